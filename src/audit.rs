@@ -1,4 +1,17 @@
-use crate::{read_config_file, read_shell, shell};
+use crate::{
+    audit_dependencies,
+    audit_history::{self, AuditRun, History},
+    audit_identity::CommitIdentity,
+    audit_ledger::{self, Ledger, Target},
+    audit_policy::{Approval, PullRequestInfo},
+    audit_report::{self, OutputFormat},
+    audit_review,
+    audit_risk::{self, Risk},
+    audit_unsafe::{self, UnsafeChurnPerCrate},
+    cargo_lock,
+    changelog::{self, ChangelogPullRequest},
+    read_config_file, read_shell, shell, GithubProject, Vcs, DEFAULT_WGPU_REPOSITORY,
+};
 use clap::Parser;
 use octocrab::{
     models::{
@@ -7,15 +20,21 @@ use octocrab::{
     },
     Octocrab,
 };
+use serde_derive::{Deserialize, Serialize};
 use std::{
+    collections::BTreeSet,
     fs::File,
     io::{self, BufWriter, Read, Write},
     path::{Path, PathBuf},
+    process::Command,
+    str::FromStr,
     sync::Arc,
 };
 
 #[derive(Parser, Debug)]
 pub struct AuditArgs {
+    #[clap(subcommand)]
+    sub_command: Option<SubCommand>,
     #[arg(long)]
     config: Option<PathBuf>,
     /// Start of the commit range.
@@ -23,15 +42,115 @@ pub struct AuditArgs {
     /// If not specified, this script will look into ./latest-commit.txt
     #[arg(long)]
     from: Option<String>,
+    /// Start the commit range at the `wgpu` revision currently in gecko's `Cargo.lock`.
+    ///
+    /// Combined with `--to <rev>` (required), this covers exactly the commits that
+    /// `moz-wgpu wgpu-update --git-hash <rev>` would import. The latest audited commit
+    /// is not updated in this mode.
+    #[arg(long, conflicts_with = "from", requires = "to")]
+    from_gecko: bool,
     /// End of the commit range (defaults to HEAD).
     #[arg(long)]
     to: Option<String>,
-    /// Optionally write the resulting csv into a file (defaults to stdout)
+    /// Optionally write the resulting report into a file (defaults to stdout)
     #[arg(short, long)]
     output: Option<PathBuf>,
+    /// The format of the report.
+    #[arg(long, value_enum, default_value_t)]
+    format: OutputFormat,
     /// Whether to pull changes and checkout the main branch.
     #[arg(long)]
     pull: bool,
+    /// Optionally write the changelog in markdown into a file.
+    #[arg(long)]
+    changelog: Option<PathBuf>,
+}
+
+#[derive(Parser, Debug)]
+pub enum SubCommand {
+    /// Record in the audit ledger that commits have been vetted.
+    Mark {
+        /// Commit hashes (or prefixes) and/or pull request numbers (for example `#3435` or `pr:3435`).
+        #[arg(required = true)]
+        targets: Vec<String>,
+        /// The name of the person who vetted the commits.
+        #[arg(long)]
+        by: String,
+    },
+    /// Step through the unvetted commits of the audit ledger and record for each of them whether
+    /// it is vetted or needs a follow-up.
+    Review {
+        /// The name of the person reviewing the commits.
+        #[arg(long)]
+        by: String,
+        /// Start of the commit range.
+        ///
+        /// If not specified, all unvetted commits of the ledger are reviewed.
+        #[arg(long)]
+        from: Option<String>,
+        /// End of the commit range (defaults to HEAD).
+        #[arg(long)]
+        to: Option<String>,
+    },
+    /// Show the commits that still need to be vetted according to the audit ledger.
+    Status {
+        /// Start of the commit range.
+        ///
+        /// If not specified, all unvetted commits of the ledger are shown.
+        #[arg(long)]
+        from: Option<String>,
+        /// End of the commit range (defaults to HEAD).
+        #[arg(long)]
+        to: Option<String>,
+    },
+    /// Import rows exported from the `wgpu-vet` spreadsheet into the audit ledger.
+    Import {
+        /// A tab separated file with the columns `pull request`, `commit`, `author`,
+        /// `reviewers`, `merger`, `vetted by`.
+        file: PathBuf,
+    },
+    /// Show the ranges that were audited, with the gaps and overlaps between them.
+    History {
+        #[clap(subcommand)]
+        action: Option<HistoryCommand>,
+    },
+    /// List the third-party crates updated in gecko's `Cargo.lock`, with the `cargo vet`
+    /// command to audit each of them.
+    ///
+    /// The working copy is compared against `--base`.
+    Dependencies {
+        /// The gecko revision to compare against (defaults to the parent of the local `wgpu`
+        /// update commit, or of the working copy if there is none).
+        #[arg(long)]
+        base: Option<String>,
+    },
+    /// Append the ledger entries to a file in the `wgpu-vet` spreadsheet format.
+    ///
+    /// Commits that are already in the file are not written again.
+    Export {
+        /// The tab separated file to append to. It is created if it does not exist.
+        file: PathBuf,
+    },
+}
+
+#[derive(Parser, Debug)]
+pub enum HistoryCommand {
+    /// List the audits whose range contains a commit.
+    Covers {
+        /// The commit hash (or any revision git understands).
+        commit: String,
+    },
+    /// Re-generate the report of a previous audit from the audit ledger.
+    Regenerate {
+        /// The index of the audit, as shown by `moz-wgpu audit history`.
+        index: usize,
+        /// Optionally write the report into a file (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// The format of the report.
+        #[arg(long, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
 }
 
 struct Github {
@@ -71,12 +190,28 @@ impl Github {
     }
 }
 
-fn git_rev_list(path: &Path, from: &str, to: &str) -> io::Result<Vec<String>> {
+/// Run a git command and return its output, or an error if git exits with a failure status.
+pub fn git_output(path: &Path, args: &[&str]) -> io::Result<String> {
+    progress!(" -- Running \"git {}\"", args.join(" "));
+
+    let output = Command::new("git").args(args).current_dir(path).output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+pub fn git_rev_list(path: &Path, from: &str, to: &str) -> io::Result<Vec<String>> {
     if from == to {
         return Ok(Vec::new());
     }
 
-    let text = read_shell(path, "git", &["rev-list", &format!("{from}..{to}")]).stdout;
+    let text = git_output(path, &["rev-list", &format!("{from}..{to}")])?;
 
     let mut result = Vec::new();
     for line in text.split('\n') {
@@ -88,6 +223,66 @@ fn git_rev_list(path: &Path, from: &str, to: &str) -> io::Result<Vec<String>> {
     Ok(result)
 }
 
+/// The merge commits between `from` and `to`.
+pub fn git_merge_commits(path: &Path, from: &str, to: &str) -> io::Result<BTreeSet<String>> {
+    if from == to {
+        return Ok(BTreeSet::new());
+    }
+
+    let text = git_output(path, &["rev-list", "--merges", &format!("{from}..{to}")])?;
+
+    Ok(text.lines().map(str::to_string).collect())
+}
+
+/// A commit of the first-parent history of a range, with the commits it merged if it is a
+/// merge commit.
+pub struct RangeCommit {
+    pub hash: String,
+    pub merged: Vec<String>,
+}
+
+/// The first-parent history between `from` and `to` (newest first), where merge commits list the
+/// commits that they brought in.
+pub fn git_first_parent_range(path: &Path, from: &str, to: &str) -> io::Result<Vec<RangeCommit>> {
+    if from == to {
+        return Ok(Vec::new());
+    }
+
+    let output = git_output(
+        path,
+        &[
+            "rev-list",
+            "--first-parent",
+            "--parents",
+            &format!("{from}..{to}"),
+        ],
+    )?;
+
+    let mut result = Vec::new();
+    for line in output.lines() {
+        let mut hashes = line.split_whitespace();
+        let Some(hash) = hashes.next() else {
+            continue;
+        };
+
+        let merged = if hashes.count() > 1 {
+            git_rev_list(path, &format!("{hash}^1"), hash)?
+                .into_iter()
+                .filter(|merged| merged != hash)
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        result.push(RangeCommit {
+            hash: hash.to_string(),
+            merged,
+        });
+    }
+
+    Ok(result)
+}
+
 pub fn read_latest_audit(path: &Path) -> io::Result<String> {
     let mut file = File::open(path)?;
     let mut result = String::new();
@@ -97,17 +292,371 @@ pub fn read_latest_audit(path: &Path) -> io::Result<String> {
     Ok(result.trim().to_string())
 }
 
-struct Commit {
-    pull_request: Option<u64>,
-    pull_request_title: String,
-    hash: String,
-    author: String,
-    reviewers: Vec<String>,
-    merger: Option<String>,
-    vetted_by: Vec<String>,
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Commit {
+    pub pull_request: Option<u64>,
+    pub pull_request_title: String,
+    pub hash: String,
+    pub author: String,
+    pub reviewers: Vec<String>,
+    pub merger: Option<String>,
+    /// The people who vetted the commit manually, with `moz-wgpu audit mark` or by importing the
+    /// `wgpu-vet` spreadsheet.
+    pub vetted_by: Vec<String>,
+    /// The people who vetted the commit according to the trust policy. Unlike `vetted_by`, this
+    /// is recomputed every time the commit is audited.
+    pub policy_vetted_by: Vec<String>,
+    /// Why the commit was automatically considered vetted, if it was.
+    pub vetting_reasons: Vec<String>,
+    pub risk: Risk,
+    /// Unsafe code added and removed by the commit, per crate.
+    pub unsafe_churn: UnsafeChurnPerCrate,
+    pub is_merge: bool,
+    /// For commits that are not on the first-parent history of the range, the merge commit that
+    /// brought them in.
+    pub merged_in: Option<String>,
+    /// Signature and identity of the commit's author and committer.
+    pub identity: CommitIdentity,
+    /// Why the commit needs more attention, if an auditor flagged it with `moz-wgpu audit review`.
+    pub follow_up: Option<String>,
+}
+
+impl Commit {
+    /// Everyone who vetted the commit, manually or according to the trust policy.
+    pub fn vetters(&self) -> Vec<&str> {
+        let mut vetters: Vec<&str> = self.vetted_by.iter().map(String::as_str).collect();
+        for name in &self.policy_vetted_by {
+            if !vetters.contains(&name.as_str()) {
+                vetters.push(name);
+            }
+        }
+
+        vetters
+    }
+
+    pub fn is_vetted(&self) -> bool {
+        !self.vetted_by.is_empty() || !self.policy_vetted_by.is_empty()
+    }
+
+    /// Commits that landed without being reviewed in a pull request deserve the most attention.
+    pub fn landed_without_pull_request(&self) -> bool {
+        self.pull_request.is_none() && !self.is_merge
+    }
+}
+
+pub fn command(args: &AuditArgs) -> io::Result<()> {
+    match &args.sub_command {
+        None => find_commits_to_audit(args),
+        Some(SubCommand::Mark { targets, by }) => mark_command(args, targets, by),
+        Some(SubCommand::Status { from, to }) => status_command(args, from, to),
+        Some(SubCommand::Review { by, from, to }) => review_command(args, by, from, to),
+        Some(SubCommand::Import { file }) => import_command(args, file),
+        Some(SubCommand::Export { file }) => export_command(args, file),
+        Some(SubCommand::History { action }) => history_command(args, action),
+        Some(SubCommand::Dependencies { base }) => dependencies_command(args, base),
+    }
+}
+
+fn dependencies_command(args: &AuditArgs, base: &Option<String>) -> io::Result<()> {
+    let config = read_config_file(&args.config)?;
+    let vcs = config
+        .gecko
+        .vcs
+        .as_deref()
+        .map(Vcs::from_str)
+        .transpose()
+        .map_err(io::Error::other)?
+        .unwrap_or_default();
+
+    let base = match base {
+        Some(base) => base.clone(),
+        None => {
+            let base = audit_dependencies::default_base(&config.gecko.path, vcs);
+            progress!("Comparing against {base}");
+            base
+        }
+    };
+
+    let updates = audit_dependencies::updates_since(&config.gecko.path, vcs, &base)?;
+
+    progress!("\n# Third-party crates to audit\n");
+    print!("{}", audit_dependencies::format_checklist(&updates));
+    progress!();
+
+    Ok(())
+}
+
+fn history_command(args: &AuditArgs, action: &Option<HistoryCommand>) -> io::Result<()> {
+    let config = read_config_file(&args.config)?;
+    let project = &config.wgpu;
+    let history = History::load(&audit_history::history_path(project))?;
+
+    match action {
+        None => {
+            history.print();
+            history.print_gaps_and_overlaps(&project.path)
+        }
+        Some(HistoryCommand::Covers { commit }) => {
+            let audits = history.audits_covering(&project.path, commit)?;
+            if audits.is_empty() {
+                progress!("{commit} was not covered by any audit.");
+            }
+            for idx in audits {
+                let run = &history.audits[idx];
+                progress!(
+                    "{commit} was covered by audit {idx} ({}..{}) on {} by {}",
+                    run.from,
+                    run.to,
+                    run.date,
+                    run.user
+                );
+            }
+            Ok(())
+        }
+        Some(HistoryCommand::Regenerate {
+            index,
+            output,
+            format,
+        }) => {
+            if output.is_none() && format.is_machine_readable() {
+                crate::send_progress_to_stderr();
+            }
+
+            let Some(run) = history.audits.get(*index) else {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("There is no audit {index} in the history"),
+                ));
+            };
+
+            let ledger = Ledger::load(&audit_ledger::ledger_path(project))?;
+            let mut commits = Vec::new();
+            for hash in git_rev_list(&project.path, &run.from, &run.to)?
+                .iter()
+                .rev()
+            {
+                commits.extend(ledger.entries_for(hash).cloned());
+            }
+
+            write_output(&commits, *format, output)
+        }
+    }
+}
+
+fn import_command(args: &AuditArgs, path: &Path) -> io::Result<()> {
+    let config = read_config_file(&args.config)?;
+    let ledger_path = audit_ledger::ledger_path(&config.wgpu);
+    let mut ledger = Ledger::load(&ledger_path)?;
+
+    let rows = audit_report::read_tsv(io::BufReader::new(File::open(path)?))?;
+    let row_count = rows.len();
+    let added = ledger.import(rows);
+    ledger.save(&ledger_path)?;
+
+    progress!(
+        "Imported {row_count} rows from {path:?}: {added} new entries, {} already in {ledger_path:?}",
+        row_count - added
+    );
+
+    Ok(())
+}
+
+fn export_command(args: &AuditArgs, path: &Path) -> io::Result<()> {
+    let config = read_config_file(&args.config)?;
+    let ledger = Ledger::load(&audit_ledger::ledger_path(&config.wgpu))?;
+
+    let existing_text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+    let existing = audit_report::read_tsv(existing_text.as_bytes())?;
+
+    let new_rows: Vec<Commit> = ledger
+        .commits
+        .iter()
+        .filter(|entry| !existing.iter().any(|row| row.hash == entry.hash))
+        .cloned()
+        .collect();
+
+    let mut file = BufWriter::new(
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?,
+    );
+    if !existing_text.is_empty() && !existing_text.ends_with('\n') {
+        writeln!(file)?;
+    }
+    audit_report::write_tsv(&new_rows, &mut file)?;
+    file.flush()?;
+
+    progress!(
+        "Appended {} rows to {path:?} ({} commits were already present)",
+        new_rows.len(),
+        ledger.commits.len() - new_rows.len()
+    );
+
+    Ok(())
+}
+
+fn mark_command(args: &AuditArgs, targets: &[String], name: &str) -> io::Result<()> {
+    let config = read_config_file(&args.config)?;
+    let ledger_path = audit_ledger::ledger_path(&config.wgpu);
+    let mut ledger = Ledger::load(&ledger_path)?;
+
+    // Validate all the targets before touching the ledger so that a typo does not leave the
+    // command half done.
+    let mut parsed = Vec::new();
+    for target in targets {
+        let parsed_target = Target::parse(target)
+            .map_err(|msg| io::Error::new(io::ErrorKind::InvalidInput, msg))?;
+        if ledger.count_matches(&parsed_target) == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{target:?} is not in the audit ledger {ledger_path:?}, run `moz-wgpu audit` first"),
+            ));
+        }
+        parsed.push((target, parsed_target));
+    }
+
+    for (target, parsed_target) in &parsed {
+        let count = ledger.mark(parsed_target, name);
+        progress!("Marked {count} ledger entries for {target:?} as vetted by {name}");
+    }
+
+    ledger.save(&ledger_path)
+}
+
+fn review_command(
+    args: &AuditArgs,
+    name: &str,
+    from: &Option<String>,
+    to: &Option<String>,
+) -> io::Result<()> {
+    let config = read_config_file(&args.config)?;
+    let ledger_path = audit_ledger::ledger_path(&config.wgpu);
+    let mut ledger = Ledger::load(&ledger_path)?;
+
+    let unvetted = match from {
+        Some(from) => {
+            let to = to.as_deref().unwrap_or("HEAD");
+            range_status(&config.wgpu, &ledger, from, to)?.unvetted
+        }
+        None => ledger
+            .commits
+            .iter()
+            .filter(|entry| !entry.is_vetted())
+            .cloned()
+            .collect(),
+    };
+
+    audit_review::review(
+        &config.wgpu.path,
+        &mut ledger,
+        &ledger_path,
+        &unvetted,
+        name,
+    )?;
+
+    let hashes: BTreeSet<&str> = unvetted.iter().map(|entry| entry.hash.as_str()).collect();
+    let reviewed: Vec<Commit> = ledger
+        .commits
+        .iter()
+        .filter(|entry| hashes.contains(entry.hash.as_str()))
+        .cloned()
+        .collect();
+
+    if args.output.is_some() {
+        write_output(&reviewed, args.format, &args.output)?;
+    }
+
+    Ok(())
+}
+
+fn status_command(args: &AuditArgs, from: &Option<String>, to: &Option<String>) -> io::Result<()> {
+    let config = read_config_file(&args.config)?;
+    let ledger_path = audit_ledger::ledger_path(&config.wgpu);
+    let ledger = Ledger::load(&ledger_path)?;
+
+    let Some(from) = from else {
+        print_audit_list(&ledger.commits)?;
+        return Ok(());
+    };
+
+    let to = to.as_deref().unwrap_or("HEAD");
+    let status = range_status(&config.wgpu, &ledger, from, to)?;
+    print_range_status(&status, from, to)
+}
+
+/// The vetting state of a range of commits according to the audit ledger.
+pub struct RangeStatus {
+    pub commit_count: usize,
+    /// Ledger entries of the commits that nobody vetted.
+    pub unvetted: Vec<Commit>,
+    /// Commits of the range that were never added to the ledger.
+    pub missing: Vec<String>,
+}
+
+impl RangeStatus {
+    pub fn is_complete(&self) -> bool {
+        self.unvetted.is_empty() && self.missing.is_empty()
+    }
+}
+
+pub fn range_status(
+    project: &GithubProject,
+    ledger: &Ledger,
+    from: &str,
+    to: &str,
+) -> io::Result<RangeStatus> {
+    let rev_list = git_rev_list(&project.path, from, to)?;
+
+    let mut missing = Vec::new();
+    let mut unvetted = Vec::new();
+    for hash in rev_list.iter().rev() {
+        let entries: Vec<&Commit> = ledger.entries_for(hash).collect();
+
+        if entries.is_empty() {
+            missing.push(hash.clone());
+        } else if !entries.iter().any(|entry| entry.is_vetted()) {
+            unvetted.extend(entries.into_iter().cloned());
+        }
+    }
+
+    Ok(RangeStatus {
+        commit_count: rev_list.len(),
+        unvetted,
+        missing,
+    })
+}
+
+pub fn print_range_status(status: &RangeStatus, from: &str, to: &str) -> io::Result<()> {
+    print_audit_list(&status.unvetted)?;
+
+    if !status.missing.is_empty() {
+        progress!("# Commits missing from the audit ledger (run `moz-wgpu audit --from {from} --to {to}`)\n");
+        for hash in &status.missing {
+            progress!(" * Commit: https://github.com/gfx-rs/wgpu/commit/{hash}");
+        }
+        progress!();
+    }
+
+    progress!(
+        "{} of {} commits in {from}..{to} are not vetted yet.",
+        status.unvetted.len() + status.missing.len(),
+        status.commit_count
+    );
+
+    Ok(())
 }
 
 pub fn find_commits_to_audit(args: &AuditArgs) -> io::Result<()> {
+    if args.output.is_none() && args.format.is_machine_readable() {
+        crate::send_progress_to_stderr();
+    }
+
     let config = read_config_file(&args.config)?;
 
     let project = &config.wgpu;
@@ -117,10 +666,33 @@ pub fn find_commits_to_audit(args: &AuditArgs) -> io::Result<()> {
         .clone()
         .unwrap_or_else(|| PathBuf::from("./latest-commit.txt"));
 
-    let start_commit = args
-        .from
-        .clone()
-        .unwrap_or_else(|| read_latest_audit(&latest_commit_path).unwrap());
+    let history_path = audit_history::history_path(project);
+    let mut history = History::load(&history_path)?;
+
+    let start_commit = if args.from_gecko {
+        let version = cargo_lock::find_version("wgpu-core", &config.gecko.path)?;
+        if version.git_hash.is_empty() {
+            return Err(io::Error::other(format!(
+                "`wgpu-core` {} is not imported from git in gecko's Cargo.lock",
+                version.semver
+            )));
+        }
+        progress!(
+            "Auditing from the `wgpu` revision in gecko: {}",
+            version.git_hash
+        );
+        version.git_hash
+    } else if let Some(from) = &args.from {
+        from.clone()
+    } else if let Some(latest) = history.latest(&project.path) {
+        progress!(
+            "Starting from the end of the previous audit ({})",
+            latest.date
+        );
+        latest.to.clone()
+    } else {
+        read_latest_audit(&latest_commit_path)?
+    };
 
     let end_commit = args.to.clone().unwrap_or_else(|| "HEAD".to_string());
 
@@ -148,95 +720,189 @@ pub fn find_commits_to_audit(args: &AuditArgs) -> io::Result<()> {
     let rev_list = git_rev_list(&project.path, &start_commit, &end_commit)?;
 
     if rev_list.is_empty() {
-        println!("No new commits since {start_commit}, nothing to do.");
+        progress!("No new commits since {start_commit}, nothing to do.");
         return Ok(());
     }
 
+    let mainline = git_first_parent_range(&project.path, &start_commit, &end_commit)?;
+
     let mut commits = Vec::new();
-    let mut found_at_least_one_pr = false;
 
-    let mut changelog = Vec::new();
+    let mut changelog: Vec<ChangelogPullRequest> = Vec::new();
+    // The pull request each commit belongs to, to find the crates touched by the pull requests.
+    let mut changelog_hashes: Vec<(String, u64)> = Vec::new();
+    let mut add_to_changelog = |hash: &str, pull_requests: Vec<ChangelogPullRequest>| {
+        for pr in pull_requests {
+            changelog_hashes.push((hash.to_string(), pr.number));
+            if !changelog.iter().any(|item| item.number == pr.number) {
+                changelog.push(pr);
+            }
+        }
+    };
 
-    for commit_hash in rev_list.iter().rev() {
-        println!("{commit_hash}");
-
-        let pulls = pull_requests_for_commit(&github, commit_hash);
-
-        if pulls.is_empty() {
-            println!("Found no pull request for this commit");
-            // This is less common but it can happen that commits are made without pull a request.
-            commits.push(Commit {
-                pull_request: None,
-                pull_request_title: String::new(),
-                author: String::new(),
-                reviewers: Vec::new(),
-                merger: None,
-                hash: commit_hash.clone(),
-                vetted_by: Vec::new(),
-            });
+    for range_commit in mainline.iter().rev() {
+        let (mut entries, pull_requests) = audit_commit(&github, project, &range_commit.hash);
+        add_to_changelog(&range_commit.hash, pull_requests.clone());
+
+        if range_commit.merged.is_empty() {
+            commits.extend(entries);
+            continue;
         }
 
-        for pull in pulls {
-            found_at_least_one_pr = true;
-            let author = pull.user.clone().map(|user| user.login).unwrap_or_default();
-            let title = pull.title.clone().unwrap_or_default();
-            let number = pull.number;
-
-            changelog.push(format!(" * #{number} {title}\n   By {author} in https://github.com/gfx-rs/wgpu/pull/{number}"));
-
-            let mut commit = Commit {
-                pull_request: Some(pull.number),
-                pull_request_title: title.clone(),
-                author,
-                hash: commit_hash.clone(),
-                reviewers: reviewers_for_pull_request(&github, pull.number),
-                merger: merger_for_pull_request(&github, pull.number),
-                vetted_by: Vec::new(),
-            };
+        for entry in &mut entries {
+            entry.is_merge = true;
+        }
 
-            fn maybe_add_vetter(vetted_by: &mut Vec<String>, trusted: &[String], name: &String) {
-                if trusted.contains(name) && !vetted_by.contains(name) {
-                    vetted_by.push(name.to_string());
+        // Commits brought in by a merge are attributed to the pull request of the merge if
+        // there is one. Otherwise (typically a release branch merged without a pull request),
+        // look for their own pull requests.
+        let mut merged_commits = Vec::new();
+        for merged_hash in range_commit.merged.iter().rev() {
+            if !pull_requests.is_empty() {
+                let identity = identity_for_commit(&github, merged_hash);
+                for entry in entries.iter().filter(|entry| entry.pull_request.is_some()) {
+                    // The merge's pull request was not authored by the people who made the
+                    // commits it brings in, their identity is not compared with its author.
+                    let mut identity = identity.clone();
+                    identity.check(&project.path, merged_hash, None);
+                    merged_commits.push(Commit {
+                        hash: merged_hash.clone(),
+                        is_merge: false,
+                        merged_in: Some(range_commit.hash.clone()),
+                        identity,
+                        ..entry.clone()
+                    });
                 }
+                add_to_changelog(merged_hash, pull_requests.clone());
+                continue;
             }
 
-            maybe_add_vetter(
-                &mut commit.vetted_by,
-                &project.trusted_reviewers,
-                &commit.author,
-            );
-            for reviewer in &commit.reviewers {
-                maybe_add_vetter(&mut commit.vetted_by, &project.trusted_reviewers, reviewer);
-            }
-            if let Some(merger) = &commit.merger {
-                maybe_add_vetter(&mut commit.vetted_by, &project.trusted_reviewers, merger);
+            let (merged_entries, merged_pull_requests) =
+                audit_commit(&github, project, merged_hash);
+            add_to_changelog(merged_hash, merged_pull_requests);
+            for entry in merged_entries {
+                merged_commits.push(Commit {
+                    merged_in: Some(range_commit.hash.clone()),
+                    ..entry
+                });
             }
-
-            commits.push(commit);
         }
+
+        commits.extend(entries);
+        commits.extend(merged_commits);
     }
 
+    let found_at_least_one_pr = commits.iter().any(|commit| commit.pull_request.is_some());
+
     if !found_at_least_one_pr {
-        println!();
-        println!("Now that's odd. We found commits locally via git rev-list but we couldn't get pull requests from the web API.");
-        println!("This could mean:");
-        println!(" - That commits have been merged without pull requests.");
-        println!(" - Or your github authentication token has expired.");
+        progress!();
+        progress!("Now that's odd. We found commits locally via git rev-list but we couldn't get pull requests from the web API.");
+        progress!("This could mean:");
+        progress!(" - That commits have been merged without pull requests.");
+        progress!(" - Or your github authentication token has expired.");
     }
 
+    progress!("Looking for risky changes in the local checkout");
+    // The diff of a merge commit against its first parent repeats the changes of the commits
+    // it merged, which are part of the range as well, so merges do not count towards the unsafe
+    // code churn.
+    let merges = git_merge_commits(&project.path, &start_commit, &end_commit)?;
+    let mut unsafe_total = UnsafeChurnPerCrate::new();
+    for commit_hash in &rev_list {
+        let diff = audit_risk::commit_diff(&project.path, commit_hash);
+        let risk = audit_risk::assess_commit(&project.path, commit_hash, &diff);
+        let unsafe_churn = if merges.contains(commit_hash) {
+            UnsafeChurnPerCrate::new()
+        } else {
+            audit_unsafe::unsafe_churn(&diff)
+        };
+        audit_unsafe::accumulate(&mut unsafe_total, &unsafe_churn);
+
+        let crates = changelog::crates_touched(&diff);
+        for (_, number) in changelog_hashes
+            .iter()
+            .filter(|(hash, _)| hash == commit_hash)
+        {
+            if let Some(pr) = changelog.iter_mut().find(|pr| pr.number == *number) {
+                pr.crates.extend(crates.iter().cloned());
+            }
+        }
+
+        for commit in commits
+            .iter_mut()
+            .filter(|commit| &commit.hash == commit_hash)
+        {
+            commit.risk = risk.clone();
+            commit.unsafe_churn = unsafe_churn.clone();
+            if commit.landed_without_pull_request() {
+                commit.risk.score += 10;
+                commit
+                    .risk
+                    .flags
+                    .push("landed without a pull request".into());
+            }
+            for problem in commit.identity.problems() {
+                commit.risk.score += 5;
+                commit.risk.flags.push(problem);
+            }
+        }
+    }
+
+    // The breaking changes only complement the changelog, failing to extract them (for example
+    // when `CHANGELOG.md` does not exist at the start of the range) does not stop the audit.
+    let changelog_entries =
+        changelog::added_changelog_entries(&project.path, &start_commit, &end_commit)
+            .unwrap_or_else(|e| {
+                progress!("Warning: could not read the entries added to CHANGELOG.md: {e}");
+                Vec::new()
+            });
+
+    let ledger_path = audit_ledger::ledger_path(project);
+    let mut ledger = Ledger::load(&ledger_path)?;
+    let added = ledger.merge(&mut commits);
+    ledger.save(&ledger_path)?;
+    progress!("Added {added} entries to the audit ledger {ledger_path:?}");
+
     print_audit_list(&commits)?;
 
-    println!("\n\n# Changelog\n");
-    for pr in &changelog {
-        println!("{pr}");
+    progress!("# Unsafe code\n");
+    progress!(
+        "{}",
+        audit_unsafe::format_unsafe_churn(&unsafe_total).trim_end_matches('\n')
+    );
+
+    let changelog =
+        changelog::render_changelog(&changelog, &changelog_entries, DEFAULT_WGPU_REPOSITORY);
+
+    progress!("\n");
+    progress!("{changelog}");
+
+    if let Some(path) = &args.changelog {
+        progress!("Writing the changelog to {path:?}");
+        std::fs::write(path, &changelog)?;
     }
-    println!("");
 
-    write_csv_output(&commits, &args.output)?;
+    write_output(&commits, args.format, &args.output)?;
+
+    history.audits.push(AuditRun {
+        from: audit_history::resolve_rev(&project.path, &start_commit)?,
+        to: audit_history::resolve_rev(&project.path, &end_commit)?,
+        date: chrono::Utc::now().to_rfc3339(),
+        user: audit_history::current_user(&project.path),
+        output: args.output.clone(),
+        from_gecko: args.from_gecko,
+    });
+    history.save(&history_path)?;
+    progress!("Recorded the audited range in {history_path:?}");
+
+    // Auditing the range of an update does not mean that everything up to its end was audited.
+    if args.from_gecko {
+        return Ok(());
+    }
 
     if let Some(commit) = rev_list.first() {
         if let Some(path) = &project.latest_commit {
-            println!("\nSaving latest commit {commit:?} to {path:?}");
+            progress!("\nSaving latest commit {commit:?} to {path:?}");
             write!(io::BufWriter::new(File::create(path)?), "{commit}")?;
         }
     }
@@ -244,6 +910,93 @@ pub fn find_commits_to_audit(args: &AuditArgs) -> io::Result<()> {
     Ok(())
 }
 
+/// Look up the pull requests of a commit and apply the trust policy.
+///
+/// Returns one entry per pull request (or a single one without pull request), and the pull
+/// requests as they should appear in the changelog.
+fn audit_commit(
+    github: &Github,
+    project: &GithubProject,
+    commit_hash: &str,
+) -> (Vec<Commit>, Vec<ChangelogPullRequest>) {
+    progress!("{commit_hash}");
+
+    let pulls = pull_requests_for_commit(github, commit_hash);
+    let identity = identity_for_commit(github, commit_hash);
+
+    if pulls.is_empty() {
+        progress!("Found no pull request for this commit");
+        // This is less common but it can happen that commits are made without pull a request.
+        let mut identity = identity;
+        identity.check(&project.path, commit_hash, None);
+        let commit = Commit {
+            hash: commit_hash.to_string(),
+            identity,
+            ..Commit::default()
+        };
+
+        return (vec![commit], Vec::new());
+    }
+
+    let mut commits = Vec::new();
+    let mut changelog = Vec::new();
+    for pull in pulls {
+        let author = pull.user.clone().map(|user| user.login).unwrap_or_default();
+        let title = pull.title.clone().unwrap_or_default();
+
+        changelog.push(ChangelogPullRequest {
+            number: pull.number,
+            title: title.clone(),
+            author: author.clone(),
+            labels: pull
+                .labels
+                .iter()
+                .flatten()
+                .map(|label| label.name.clone())
+                .collect(),
+            crates: BTreeSet::new(),
+        });
+
+        let approvals = approvals_for_pull_request(github, pull.number);
+        let merger = merger_for_pull_request(github, pull.number);
+
+        let mut reviewers: Vec<String> = Vec::new();
+        for approval in approvals.iter().filter(|approval| !approval.dismissed) {
+            if !reviewers.contains(&approval.reviewer) {
+                reviewers.push(approval.reviewer.clone());
+            }
+        }
+
+        let vetters = project.trust_policy.vetters(
+            &project.trusted_reviewers,
+            &PullRequestInfo {
+                author: &author,
+                head_sha: Some(pull.head.sha.as_str()),
+                approvals: &approvals,
+                merger: merger.as_deref(),
+            },
+        );
+
+        let mut identity = identity.clone();
+        identity.check(&project.path, commit_hash, Some(&author));
+
+        commits.push(Commit {
+            pull_request: Some(pull.number),
+            pull_request_title: title,
+            author,
+            hash: commit_hash.to_string(),
+            reviewers,
+            merger,
+            policy_vetted_by: vetters.iter().map(|vetter| vetter.name.clone()).collect(),
+            vetting_reasons: vetters.into_iter().map(|vetter| vetter.reason).collect(),
+            identity,
+            ..Commit::default()
+        });
+    }
+
+    (commits, changelog)
+}
+
 fn pull_requests_for_commit(github: &Github, commit: &str) -> Vec<PullRequest> {
     let request = github.runtime.block_on(
         github
@@ -264,7 +1017,8 @@ fn pull_requests_for_commit(github: &Github, commit: &str) -> Vec<PullRequest> {
         .unwrap_or_default()
 }
 
-fn reviewers_for_pull_request(github: &Github, pr: u64) -> Vec<String> {
+/// The approving reviews of a pull request. Dismissed approvals are not included.
+fn approvals_for_pull_request(github: &Github, pr: u64) -> Vec<Approval> {
     let request = github.runtime.block_on(
         github
             .api
@@ -272,26 +1026,31 @@ fn reviewers_for_pull_request(github: &Github, pr: u64) -> Vec<String> {
             .list_reviews(pr),
     );
 
-    let mut reviewers = Vec::new();
+    let mut approvals = Vec::new();
     if let Ok(reviews) = request {
-        for reviewer in reviews
-            .items
-            .iter()
-            .filter(|review| review.state == Some(ReviewState::Approved))
-            .map(|review| {
-                review
-                    .user
-                    .as_ref()
-                    .map(|user| user.login.clone())
-                    .unwrap_or(String::new())
-            })
-            .filter(|user_name| !user_name.is_empty())
-        {
-            reviewers.push(reviewer)
+        for review in reviews.items.iter().filter(|review| {
+            matches!(
+                review.state,
+                Some(ReviewState::Approved | ReviewState::Dismissed)
+            )
+        }) {
+            let reviewer = review
+                .user
+                .as_ref()
+                .map(|user| user.login.clone())
+                .unwrap_or_default();
+
+            if !reviewer.is_empty() {
+                approvals.push(Approval {
+                    reviewer,
+                    commit_id: review.commit_id.clone(),
+                    dismissed: review.state == Some(ReviewState::Dismissed),
+                });
+            }
         }
     }
 
-    reviewers
+    approvals
 }
 
 fn merger_for_pull_request(github: &Github, pr_idx: u64) -> Option<String> {
@@ -311,7 +1070,7 @@ fn merger_for_pull_request(github: &Github, pr_idx: u64) -> Option<String> {
 
     // Remove the whitespaces so it's a bit nicer to read in the terminal.
     let query: String = query.chars().filter(|c| *c != ' ' && *c != '\n').collect();
-    //println!("graphql query: \"{query}\"");
+    //progress!("graphql query: \"{query}\"");
 
     let response: serde_json::Value = github.runtime.block_on(github.api.graphql(&query)).unwrap();
 
@@ -328,76 +1087,172 @@ fn merger_for_pull_request(github: &Github, pr_idx: u64) -> Option<String> {
     Some(merger)
 }
 
-fn comma_separated_string(items: &[String]) -> String {
-    let mut result = String::new();
-    for (idx, item) in items.iter().enumerate() {
-        assert!(!item.is_empty());
-        result.push_str(item);
-        if idx + 1 != items.len() {
-            result.push(',');
+/// Whether GitHub verified the signature of a commit and the GitHub accounts of its author and
+/// committer. The local signature status and the comparison with the pull request author are
+/// filled in by `CommitIdentity::check`.
+fn identity_for_commit(github: &Github, hash: &str) -> CommitIdentity {
+    let project = &github.project;
+    let org = &github.org;
+
+    let query = format!(
+        "
+        query {{
+            repository(owner:{org:?}, name:{project:?}) {{
+                object(oid:{hash:?}) {{
+                    ... on Commit {{
+                        signature {{ isValid }}
+                        author {{ user {{ login }} }}
+                        committer {{ user {{ login }} }}
+                    }}
+                }}
+            }}
+        }}"
+    );
+
+    // Unlike in `merger_for_pull_request`, the spaces can't all be removed because of the
+    // `... on Commit` fragment.
+    let query = query.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    let response: serde_json::Value = match github.runtime.block_on(github.api.graphql(&query)) {
+        Ok(response) => response,
+        Err(e) => {
+            progress!("Could not fetch the signature of {hash}: {e}");
+            return CommitIdentity::default();
         }
+    };
+
+    let Some(commit) = response
+        .get("data")
+        .and_then(|data| data.get("repository"))
+        .and_then(|repository| repository.get("object"))
+        .filter(|commit| commit.is_object())
+    else {
+        return CommitIdentity::default();
+    };
+
+    let login = |field: &str| {
+        commit
+            .get(field)?
+            .get("user")?
+            .get("login")?
+            .as_str()
+            .map(str::to_string)
+    };
+
+    CommitIdentity {
+        // Unsigned commits have a null signature.
+        verified: Some(
+            commit
+                .get("signature")
+                .and_then(|signature| signature.get("isValid"))
+                .and_then(|valid| valid.as_bool())
+                .unwrap_or(false),
+        ),
+        author_login: login("author"),
+        committer_login: login("committer"),
+        ..CommitIdentity::default()
     }
+}
 
-    result
+pub fn print_audit_item(item: &Commit) {
+    if let Some(pr_num) = &item.pull_request {
+        progress!(
+            " * #{pr_num}: {} By {}\n   In https://github.com/gfx-rs/wgpu/pull/{pr_num}\n   Commit: https://github.com/gfx-rs/wgpu/commit/{}",
+            item.pull_request_title,
+            item.author,
+            item.hash,
+        );
+    } else if item.is_merge {
+        progress!(
+            " * (Merge commit without pull request)\n   Commit: https://github.com/gfx-rs/wgpu/commit/{}",
+            item.hash,
+        );
+    } else {
+        progress!(
+            " * (No pull request)\n   Commit: https://github.com/gfx-rs/wgpu/commit/{}",
+            item.hash,
+        );
+    }
+
+    if let Some(merge) = &item.merged_in {
+        progress!("   Merged in: https://github.com/gfx-rs/wgpu/commit/{merge}");
+    }
+
+    if let Some(note) = &item.follow_up {
+        progress!("   Needs follow-up: {note}");
+    }
+
+    if item.risk.score > 0 {
+        progress!("   Risk score {}:", item.risk.score);
+        for flag in &item.risk.flags {
+            progress!("    - {flag}");
+        }
+    }
 }
 
 fn print_audit_list(items: &[Commit]) -> io::Result<()> {
-    println!("\n# Commits to audit\n");
+    progress!("\n# Commits to audit\n");
 
-    for item in items {
-        if !item.vetted_by.is_empty() {
-            continue;
+    // Unverified commits are listed even if they were vetted, since the vetting may have been
+    // based on an identity that the commit does not actually have.
+    let (unverified, items): (Vec<&Commit>, Vec<&Commit>) = items
+        .iter()
+        .partition(|item| !item.identity.problems().is_empty());
+
+    if !unverified.is_empty() {
+        progress!("## Unverified commits (high priority)\n");
+        for item in unverified {
+            print_audit_item(item);
+            if item.is_vetted() {
+                progress!("   Vetted by: {}", item.vetters().join(", "));
+            }
         }
+        progress!();
+    }
 
-        if let Some(pr_num) = &item.pull_request {
-            println!(
-                " * #{pr_num}: {} By {}\n   In https://github.com/gfx-rs/wgpu/pull/{pr_num}\n   Commit: https://github.com/gfx-rs/wgpu/commit/{}",
-                item.pull_request_title,
-                item.author,
-                item.hash,
-            );
-        } else {
-            println!(
-                " * (No pull request)\n   Commit: https://github.com/gfx-rs/wgpu/commit/{}",
-                item.hash,
-            );            
+    // Riskiest commits first so that they get the most attention.
+    let mut items: Vec<&Commit> = items.into_iter().filter(|item| !item.is_vetted()).collect();
+    items.sort_by_key(|item| std::cmp::Reverse(item.risk.score));
+
+    let (without_pr, with_pr): (Vec<&Commit>, Vec<&Commit>) = items
+        .into_iter()
+        .partition(|item| item.landed_without_pull_request());
+
+    if !without_pr.is_empty() {
+        progress!("## Landed without a pull request (high priority)\n");
+        for item in without_pr {
+            print_audit_item(item);
         }
+        progress!();
     }
 
-    println!("");
+    for item in with_pr {
+        print_audit_item(item);
+    }
+
+    progress!();
 
     Ok(())
 }
 
-fn write_csv_output(items: &[Commit], output: &Option<PathBuf>) -> io::Result<()> {
+fn write_output(
+    items: &[Commit],
+    format: OutputFormat,
+    output: &Option<PathBuf>,
+) -> io::Result<()> {
     let mut stdout = std::io::stdout();
-    let mut output_file = output
-        .as_ref()
-        .map(|path| BufWriter::new(File::create(path).unwrap()));
+    let mut output_file = match output {
+        Some(path) => Some(BufWriter::new(File::create(path)?)),
+        None => None,
+    };
 
     let writer = if let Some(file) = &mut output_file {
         file as &mut dyn Write
     } else {
-        println!("");
+        progress!();
         &mut stdout as &mut dyn Write
     };
 
-    for item in items {
-        let pull_request = item
-            .pull_request
-            .map(|num| format!("{num}"))
-            .unwrap_or_default();
-        writeln!(
-            writer,
-            "{}\t{}\t{}\t{}\t{}\t{}",
-            pull_request,
-            item.hash,
-            item.author,
-            comma_separated_string(&item.reviewers),
-            item.merger.clone().unwrap_or_default(),
-            comma_separated_string(&item.vetted_by),
-        )?;
-    }
-
-    Ok(())
+    audit_report::write_report(items, DEFAULT_WGPU_REPOSITORY, format, writer)?;
+    writer.flush()
 }
@@ -0,0 +1,199 @@
+use crate::{
+    cargo_lock::{self, LockedPackage},
+    cargo_toml, read_shell, Vcs,
+};
+use std::{cmp::Ordering, fmt::Write, fs::File, io, path::Path};
+
+/// A third-party crate whose version changed in gecko's `Cargo.lock`.
+#[derive(Clone, Debug)]
+pub struct DependencyUpdate {
+    pub name: String,
+    /// `None` if the crate was not in `Cargo.lock` before.
+    pub prev: Option<String>,
+    pub next: String,
+    /// The source repository, from the crates.io metadata in the vendored `Cargo.toml`.
+    pub repository: Option<String>,
+}
+
+impl DependencyUpdate {
+    /// The `cargo vet` command that shows the code to audit.
+    pub fn cargo_vet_command(&self) -> String {
+        match &self.prev {
+            Some(prev) => format!("cargo vet diff {} {prev} {}", self.name, self.next),
+            None => format!("cargo vet inspect {} {}", self.name, self.next),
+        }
+    }
+}
+
+/// The content of gecko's `Cargo.lock` at a revision of the gecko repository.
+pub fn cargo_lock_at(gecko_path: &Path, vcs: Vcs, rev: &str) -> io::Result<String> {
+    let output = match vcs {
+        Vcs::Mercurial => read_shell(gecko_path, "hg", &["cat", "-r", rev, "Cargo.lock"]),
+        Vcs::Git => read_shell(gecko_path, "git", &["show", &format!("{rev}:Cargo.lock")]),
+    };
+
+    if output.stdout.trim().is_empty() {
+        return Err(io::Error::other(format!(
+            "Could not read Cargo.lock at revision {rev:?}: {}",
+            output.stderr.trim()
+        )));
+    }
+
+    Ok(output.stdout)
+}
+
+/// The revision to compare the working copy against by default: the parent of the last `wgpu`
+/// update commit that was not pushed yet (the commits made by `wgpu-update`), or the parent of
+/// the working copy if there is none.
+pub fn default_base(gecko_path: &Path, vcs: Vcs) -> String {
+    const UPDATE_MESSAGE: &str = "Update `wgpu` to revision";
+    let output = match vcs {
+        Vcs::Mercurial => read_shell(
+            gecko_path,
+            "hg",
+            &[
+                "log",
+                "-r",
+                &format!("last(draft() and ancestors(.) and desc({UPDATE_MESSAGE:?}))"),
+                "-T",
+                "{node}",
+            ],
+        ),
+        Vcs::Git => read_shell(
+            gecko_path,
+            "git",
+            &[
+                "log",
+                "-n1",
+                "--format=%H",
+                "--fixed-strings",
+                &format!("--grep={UPDATE_MESSAGE}"),
+                "HEAD",
+                "--not",
+                "--remotes",
+            ],
+        ),
+    };
+
+    match (output.stdout.trim(), vcs) {
+        ("", Vcs::Mercurial) => ".".to_string(),
+        ("", Vcs::Git) => "HEAD".to_string(),
+        (update, _) => format!("{update}^"),
+    }
+}
+
+/// Compare versions numerically component by component, so that `0.10.0` is after `0.9.3`.
+fn compare_versions(a: &str, b: &str) -> Ordering {
+    let components = |version: &str| -> Vec<u64> {
+        version
+            .split(['.', '-', '+'])
+            .map(|component| component.parse().unwrap_or(0))
+            .collect()
+    };
+
+    components(a).cmp(&components(b))
+}
+
+/// The crates.io packages that were added or changed version between two `Cargo.lock` files.
+///
+/// When several versions of a crate are locked, each new version is compared against the most
+/// recent version that went away, if any.
+pub fn dependency_updates(prev: &[LockedPackage], next: &[LockedPackage]) -> Vec<DependencyUpdate> {
+    let versions = |packages: &[LockedPackage], name: &str| -> Vec<String> {
+        packages
+            .iter()
+            .filter(|package| package.is_from_registry() && package.name == name)
+            .map(|package| package.version.clone())
+            .collect()
+    };
+
+    let mut updates = Vec::new();
+    for package in next.iter().filter(|package| package.is_from_registry()) {
+        let prev_versions = versions(prev, &package.name);
+        if prev_versions.contains(&package.version) {
+            continue;
+        }
+
+        let next_versions = versions(next, &package.name);
+        let prev = prev_versions
+            .into_iter()
+            .filter(|version| !next_versions.contains(version))
+            .max_by(|a, b| compare_versions(a, b));
+
+        updates.push(DependencyUpdate {
+            name: package.name.clone(),
+            prev,
+            next: package.version.clone(),
+            repository: None,
+        });
+    }
+
+    updates
+}
+
+/// Read the source repository (or the homepage if there is none) from the `Cargo.toml` of a
+/// crate vendored in `third_party/rust`.
+pub fn vendored_repository(gecko_path: &Path, name: &str, version: &str) -> Option<String> {
+    // `cargo vendor` adds the version to the directory name when several versions of a crate
+    // are vendored.
+    for dir in [format!("{name}-{version}"), name.to_string()] {
+        let path = gecko_path
+            .join("third_party/rust")
+            .join(&dir)
+            .join("Cargo.toml");
+
+        let attribute = |key: &str| {
+            let file = File::open(&path).ok()?;
+            cargo_toml::get_package_attribute(io::BufReader::new(file), key)
+                .ok()
+                .flatten()
+        };
+
+        if attribute("version").as_deref() != Some(version) {
+            continue;
+        }
+
+        return attribute("repository").or_else(|| attribute("homepage"));
+    }
+
+    None
+}
+
+/// The third-party crates updated in gecko's working copy compared to revision `base`.
+pub fn updates_since(gecko_path: &Path, vcs: Vcs, base: &str) -> io::Result<Vec<DependencyUpdate>> {
+    let prev = cargo_lock::parse_packages(&cargo_lock_at(gecko_path, vcs, base)?)?;
+    let next =
+        cargo_lock::parse_packages(&std::fs::read_to_string(gecko_path.join("Cargo.lock"))?)?;
+
+    let mut updates = dependency_updates(&prev, &next);
+    for update in &mut updates {
+        update.repository = vendored_repository(gecko_path, &update.name, &update.next);
+    }
+
+    Ok(updates)
+}
+
+/// One checklist item per updated crate, in markdown.
+pub fn format_checklist(updates: &[DependencyUpdate]) -> String {
+    let mut text = String::new();
+    if updates.is_empty() {
+        let _ = writeln!(text, "No third-party crates were updated.");
+        return text;
+    }
+
+    for update in updates {
+        let delta = match &update.prev {
+            Some(prev) => format!("{prev} -> {}", update.next),
+            None => format!("{} (new)", update.next),
+        };
+        let _ = writeln!(text, " - [ ] {} {delta}", update.name);
+        let _ = writeln!(
+            text,
+            "   Repository: {}",
+            update.repository.as_deref().unwrap_or("unknown")
+        );
+        let _ = writeln!(text, "   Audit with: `{}`", update.cargo_vet_command());
+    }
+
+    text
+}
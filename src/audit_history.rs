@@ -0,0 +1,329 @@
+use crate::{
+    audit::{git_output, git_rev_list},
+    read_shell, GithubProject,
+};
+use serde_derive::{Deserialize, Serialize};
+use std::{
+    collections::BTreeSet,
+    fs::File,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+/// A log of the ranges that were audited, so that gaps and overlaps between audits can be found
+/// and old reports re-generated.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct History {
+    #[serde(default)]
+    pub audits: Vec<AuditRun>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct AuditRun {
+    /// Full hash of the start of the range (excluded).
+    pub from: String,
+    /// Full hash of the end of the range (included).
+    pub to: String,
+    /// When the audit was run, in RFC 3339 format.
+    pub date: String,
+    /// Who ran the audit.
+    pub user: String,
+    /// Where the report was written, if it was written to a file.
+    pub output: Option<PathBuf>,
+    /// Whether the range was the one of an update (`--from-gecko`), which does not mean that
+    /// everything up to its end was audited.
+    pub from_gecko: bool,
+}
+
+pub fn history_path(project: &GithubProject) -> PathBuf {
+    project
+        .audit_history
+        .clone()
+        .unwrap_or_else(|| PathBuf::from("./audit-history.toml"))
+}
+
+/// Resolve a revision (branch name, `HEAD`, short hash...) to a full commit hash.
+pub fn resolve_rev(repo: &Path, rev: &str) -> io::Result<String> {
+    let output = read_shell(
+        repo,
+        "git",
+        &["rev-parse", "--verify", &format!("{rev}^{{commit}}")],
+    );
+    let hash = output.stdout.trim();
+    if hash.is_empty() {
+        return Err(io::Error::other(format!(
+            "Could not resolve {rev:?}: {}",
+            output.stderr.trim()
+        )));
+    }
+
+    Ok(hash.to_string())
+}
+
+/// The name of the person running the tool.
+pub fn current_user(repo: &Path) -> String {
+    let name = read_shell(repo, "git", &["config", "user.name"]).stdout;
+    let name = name.trim();
+    if !name.is_empty() {
+        return name.to_string();
+    }
+
+    std::env::var("USER").unwrap_or_default()
+}
+
+impl History {
+    /// Load the history, or return an empty one if the file does not exist yet.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(History::default()),
+            Err(e) => return Err(e),
+        };
+
+        let mut buf = String::new();
+        file.read_to_string(&mut buf)?;
+
+        toml::from_str(&buf).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Failed to parse the audit history {path:?}: {e}"),
+            )
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let text = toml::to_string(self).map_err(io::Error::other)?;
+        let mut file = io::BufWriter::new(File::create(path)?);
+        file.write_all(text.as_bytes())?;
+        file.flush()
+    }
+
+    /// The audit that went the furthest in the history, which is where the next one should
+    /// start. Audits are not necessarily recorded in the order of their ranges, and the ones of
+    /// an update's range are not taken into account. Audits whose end is not in the local
+    /// checkout (shallow clone, history shared between machines...) are skipped.
+    pub fn latest(&self, repo: &Path) -> Option<&AuditRun> {
+        let mut latest = None;
+        for run in self.audits.iter().filter(|run| !run.from_gecko) {
+            let pos = match position(repo, &run.to) {
+                Ok(pos) => pos,
+                Err(e) => {
+                    progress!(
+                        "Warning: ignoring the audit that ended at {}: {e}",
+                        short(&run.to)
+                    );
+                    continue;
+                }
+            };
+            if latest.is_none_or(|(latest_pos, _)| pos >= latest_pos) {
+                latest = Some((pos, run));
+            }
+        }
+
+        latest.map(|(_, run)| run)
+    }
+
+    pub fn print(&self) {
+        println!("\n# Audited ranges\n");
+        for (idx, run) in self.audits.iter().enumerate() {
+            let output = run
+                .output
+                .as_ref()
+                .map(|path| format!(" -> {path:?}"))
+                .unwrap_or_default();
+            let from_gecko = if run.from_gecko { " (from gecko)" } else { "" };
+            println!(
+                " {idx}: {}..{}{from_gecko} on {} by {}{output}",
+                short(&run.from),
+                short(&run.to),
+                run.date,
+                run.user
+            );
+        }
+        println!();
+    }
+
+    /// Print the commits that fall between audits, and the ones that were audited more than
+    /// once.
+    pub fn print_gaps_and_overlaps(&self, repo: &Path) -> io::Result<()> {
+        println!("# Gaps and overlaps\n");
+
+        let mut found = false;
+
+        // Audits can be recorded in any order, so gaps are looked for after sorting the ranges
+        // by where they start in the history.
+        let mut ranges = Vec::new();
+        for (idx, run) in self.audits.iter().enumerate() {
+            ranges.push((position(repo, &run.from)?, position(repo, &run.to)?, idx));
+        }
+        ranges.sort();
+
+        // The end of the range that goes the furthest among the ones visited so far.
+        let mut covered: Option<(usize, usize)> = None;
+        for &(from_pos, to_pos, idx) in &ranges {
+            let run = &self.audits[idx];
+            if let Some((end_pos, end_idx)) = covered {
+                let end = &self.audits[end_idx].to;
+                let gap = if from_pos > end_pos {
+                    git_rev_list(repo, end, &run.from)?
+                } else {
+                    Vec::new()
+                };
+
+                if !gap.is_empty() {
+                    found = true;
+                    println!(
+                        " * Gap of {} commits between audits {end_idx} and {idx}: {}..{}",
+                        gap.len(),
+                        short(end),
+                        short(&run.from)
+                    );
+                }
+            }
+
+            if covered.is_none_or(|(end_pos, _)| to_pos > end_pos) {
+                covered = Some((to_pos, idx));
+            }
+        }
+
+        // Any two audits can overlap, not only the ones that follow each other.
+        let mut commits = Vec::new();
+        for run in &self.audits {
+            commits.push(
+                git_rev_list(repo, &run.from, &run.to)?
+                    .into_iter()
+                    .collect::<BTreeSet<String>>(),
+            );
+        }
+        for a in 0..commits.len() {
+            for b in (a + 1)..commits.len() {
+                let overlap = commits[a].intersection(&commits[b]).count();
+                if overlap > 0 {
+                    found = true;
+                    println!(" * Audits {a} and {b} overlap by {overlap} commits");
+                }
+            }
+        }
+
+        if !found {
+            println!("None.");
+        }
+        println!();
+
+        Ok(())
+    }
+
+    /// The audits whose range contains `commit`.
+    pub fn audits_covering(&self, repo: &Path, commit: &str) -> io::Result<Vec<usize>> {
+        let commit = resolve_rev(repo, commit)?;
+
+        let mut result = Vec::new();
+        for (idx, run) in self.audits.iter().enumerate() {
+            if git_rev_list(repo, &run.from, &run.to)?.contains(&commit) {
+                result.push(idx);
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+/// The number of commits reachable from `hash`, which orders commits along the history.
+fn position(repo: &Path, hash: &str) -> io::Result<usize> {
+    git_output(repo, &["rev-list", "--count", hash])?
+        .trim()
+        .parse()
+        .map_err(io::Error::other)
+}
+
+fn short(hash: &str) -> &str {
+    &hash[..hash.len().min(12)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    /// A git repository in a temporary directory with `count` commits, and their hashes, oldest
+    /// first.
+    fn test_repo(name: &str, count: usize) -> (PathBuf, Vec<String>) {
+        let dir = std::env::temp_dir().join(format!("moz-wgpu-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let git = |args: &[&str]| {
+            let output = Command::new("git")
+                .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+                .args(args)
+                .current_dir(&dir)
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "git {args:?} failed");
+        };
+        git(&["init", "-q"]);
+        for idx in 0..count {
+            git(&[
+                "commit",
+                "-q",
+                "--allow-empty",
+                "-m",
+                &format!("commit {idx}"),
+            ]);
+        }
+
+        let hashes = git_output(&dir, &["rev-list", "--reverse", "HEAD"])
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect();
+
+        (dir, hashes)
+    }
+
+    fn run(from: &str, to: &str, from_gecko: bool) -> AuditRun {
+        AuditRun {
+            from: from.to_string(),
+            to: to.to_string(),
+            from_gecko,
+            ..AuditRun::default()
+        }
+    }
+
+    #[test]
+    fn latest_ignores_update_ranges() {
+        let (repo, hashes) = test_repo("latest-from-gecko", 4);
+        let history = History {
+            audits: vec![
+                run(&hashes[0], &hashes[1], false),
+                run(&hashes[1], &hashes[3], true),
+            ],
+        };
+
+        let latest = history.latest(&repo).unwrap();
+        assert_eq!(latest.to, hashes[1]);
+    }
+
+    #[test]
+    fn latest_skips_unknown_commits() {
+        let (repo, hashes) = test_repo("latest-unknown", 4);
+        let unknown = "0123456789abcdef0123456789abcdef01234567";
+        // Recorded out of order, with an audit from a checkout that had other commits.
+        let history = History {
+            audits: vec![
+                run(&hashes[1], &hashes[2], false),
+                run(&hashes[2], unknown, false),
+                run(&hashes[0], &hashes[1], false),
+            ],
+        };
+
+        let latest = history.latest(&repo).unwrap();
+        assert_eq!(latest.to, hashes[2]);
+
+        let history = History {
+            audits: vec![run(&hashes[0], unknown, false)],
+        };
+        assert!(history.latest(&repo).is_none());
+    }
+}
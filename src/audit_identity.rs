@@ -0,0 +1,67 @@
+use crate::read_shell;
+use serde_derive::{Deserialize, Serialize};
+use std::path::Path;
+
+/// The GitHub account used for commits made with the web interface (for example when squashing
+/// and merging a pull request).
+const GITHUB_WEB_FLOW: &str = "web-flow";
+
+/// Who made a commit and whether that can be trusted.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CommitIdentity {
+    /// Whether GitHub verified the commit's signature. `None` if the information could not be
+    /// fetched.
+    pub verified: Option<bool>,
+    /// The signature status reported by the local git checkout (`git log --format=%G?`):
+    /// "G" good, "N" no signature, "B" bad, "U"/"X"/"Y"/"R" good with caveats, "E" cannot be
+    /// checked (typically a missing key).
+    pub local_signature: String,
+    /// GitHub account of the commit's author, if the author's email is linked to one.
+    pub author_login: Option<String>,
+    /// GitHub account of the committer, if the committer's email is linked to one.
+    pub committer_login: Option<String>,
+    /// Whether the commit's author or committer is the author of the pull request. `None` for
+    /// commits without pull request of their own, including the ones attributed to the pull
+    /// request of a merge.
+    pub matches_pr_author: Option<bool>,
+}
+
+impl CommitIdentity {
+    /// Fill in the local signature status and compare the identity with the pull request
+    /// author.
+    pub fn check(&mut self, repo: &Path, hash: &str, pr_author: Option<&str>) {
+        self.local_signature = read_shell(repo, "git", &["log", "-1", "--format=%G?", hash])
+            .stdout
+            .trim()
+            .to_string();
+
+        self.matches_pr_author = pr_author.map(|pr_author| {
+            let committer = self
+                .committer_login
+                .as_deref()
+                .filter(|login| *login != GITHUB_WEB_FLOW);
+            self.author_login.as_deref() == Some(pr_author) || committer == Some(pr_author)
+        });
+    }
+
+    /// Reasons to look at the commit more closely, empty if nothing is suspicious.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.verified == Some(false) {
+            problems.push("not verified by GitHub".to_string());
+        }
+        if self.local_signature == "B" {
+            problems.push("bad signature".to_string());
+        }
+        if self.matches_pr_author == Some(false) {
+            problems.push(format!(
+                "author ({}) and committer ({}) do not match the pull request author",
+                self.author_login.as_deref().unwrap_or("unknown"),
+                self.committer_login.as_deref().unwrap_or("unknown"),
+            ));
+        }
+
+        problems
+    }
+}
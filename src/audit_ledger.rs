@@ -0,0 +1,310 @@
+use crate::audit::Commit;
+use serde_derive::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::GithubProject;
+
+/// A local, version-controllable record of the commits that were audited and who vetted them.
+///
+/// The ledger is stored as a TOML file with one `[[commits]]` entry per (commit, pull request)
+/// pair. Running `moz-wgpu audit` adds new entries and refreshes the `policy_vetted_by` lists
+/// according to the current trust policy, and `moz-wgpu audit mark` fills the `vetted_by` lists.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Ledger {
+    #[serde(default)]
+    pub commits: Vec<Commit>,
+}
+
+pub fn ledger_path(project: &GithubProject) -> PathBuf {
+    project
+        .audit_ledger
+        .clone()
+        .unwrap_or_else(|| PathBuf::from("./audit-ledger.toml"))
+}
+
+/// Identifies the entries of the ledger to update.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Target {
+    /// A full commit hash or a prefix of it.
+    Commit(String),
+    PullRequest(u64),
+}
+
+impl Target {
+    /// Pull requests are written as `#1234` or `pr:1234`, anything else is treated as a commit
+    /// hash (prefix), including hash prefixes that only contain digits.
+    pub fn parse(src: &str) -> Result<Self, String> {
+        if let Some(num) = src.strip_prefix('#').or_else(|| src.strip_prefix("pr:")) {
+            return num
+                .parse()
+                .map(Target::PullRequest)
+                .map_err(|_| format!("Invalid pull request number {src:?}"));
+        }
+
+        if src.is_empty() || !src.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!(
+                "{src:?} is neither a commit hash nor a pull request (`#1234` or `pr:1234`)"
+            ));
+        }
+
+        Ok(Target::Commit(src.to_lowercase()))
+    }
+
+    fn matches(&self, commit: &Commit) -> bool {
+        match self {
+            Target::Commit(hash) => commit.hash.starts_with(hash.as_str()),
+            Target::PullRequest(num) => commit.pull_request == Some(*num),
+        }
+    }
+}
+
+impl Ledger {
+    /// Load the ledger, or return an empty one if the file does not exist yet.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Ledger::default()),
+            Err(e) => return Err(e),
+        };
+
+        let mut buf = String::new();
+        file.read_to_string(&mut buf)?;
+
+        toml::from_str(&buf).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Failed to parse the audit ledger {path:?}: {e}"),
+            )
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let text = toml::to_string(self).map_err(io::Error::other)?;
+        let mut file = io::BufWriter::new(File::create(path)?);
+        file.write_all(text.as_bytes())?;
+        file.flush()
+    }
+
+    pub fn entries_for<'a>(&'a self, hash: &'a str) -> impl Iterator<Item = &'a Commit> + 'a {
+        self.commits.iter().filter(move |entry| entry.hash == hash)
+    }
+
+    /// Add the commits to the ledger.
+    ///
+    /// Commits that are already present get their metadata refreshed, including who vetted them
+    /// according to the trust policy, and keep the names of people who vetted them manually. In
+    /// both cases `commits` is updated with the resulting vetters so that it reflects the state of
+    /// the ledger.
+    ///
+    /// Returns the number of new entries.
+    pub fn merge(&mut self, commits: &mut [Commit]) -> usize {
+        let mut added = 0;
+        for commit in commits.iter_mut() {
+            let existing = self.commits.iter_mut().find(|entry| {
+                entry.hash == commit.hash && entry.pull_request == commit.pull_request
+            });
+
+            match existing {
+                Some(entry) => {
+                    for name in &entry.vetted_by {
+                        if !commit.vetted_by.contains(name) {
+                            commit.vetted_by.push(name.clone());
+                        }
+                    }
+                    if commit.follow_up.is_none() {
+                        commit.follow_up = entry.follow_up.clone();
+                    }
+                    *entry = commit.clone();
+                }
+                None => {
+                    self.commits.push(commit.clone());
+                    added += 1;
+                }
+            }
+        }
+
+        added
+    }
+
+    /// Add commits that were imported from another source, such as the `wgpu-vet` spreadsheet.
+    ///
+    /// Unlike `merge`, the data already in the ledger takes precedence and is only completed
+    /// with missing information and additional vetters.
+    ///
+    /// Rows are matched with the ledger by commit hash, so that a commit is never added twice.
+    /// When the ledger has several entries for the commit (one per pull request), the one with
+    /// the same pull request as the row is updated, or else the first one.
+    ///
+    /// Returns the number of new entries.
+    pub fn import(&mut self, commits: Vec<Commit>) -> usize {
+        let mut added = 0;
+        for commit in commits {
+            let existing = self
+                .commits
+                .iter()
+                .position(|entry| {
+                    entry.hash == commit.hash && entry.pull_request == commit.pull_request
+                })
+                .or_else(|| {
+                    self.commits
+                        .iter()
+                        .position(|entry| entry.hash == commit.hash)
+                })
+                .map(|idx| &mut self.commits[idx]);
+
+            let Some(entry) = existing else {
+                self.commits.push(commit);
+                added += 1;
+                continue;
+            };
+
+            if entry.author.is_empty() {
+                entry.author = commit.author;
+            }
+            if entry.merger.is_none() {
+                entry.merger = commit.merger;
+            }
+            if entry.follow_up.is_none() {
+                entry.follow_up = commit.follow_up;
+            }
+            for reviewer in commit.reviewers {
+                if !entry.reviewers.contains(&reviewer) {
+                    entry.reviewers.push(reviewer);
+                }
+            }
+            for name in commit.vetted_by {
+                // Exported rows list the vetters from the trust policy along with the manual
+                // ones, they should not become manual marks when importing them back.
+                if !entry.vetted_by.contains(&name) && !entry.policy_vetted_by.contains(&name) {
+                    entry.vetted_by.push(name);
+                }
+            }
+        }
+
+        added
+    }
+
+    /// The number of entries matching `target`.
+    pub fn count_matches(&self, target: &Target) -> usize {
+        self.commits
+            .iter()
+            .filter(|entry| target.matches(entry))
+            .count()
+    }
+
+    /// Record that `name` vetted the entries matching `target`. This resolves any follow-up
+    /// they were flagged for.
+    ///
+    /// Returns the number of matching entries.
+    pub fn mark(&mut self, target: &Target, name: &str) -> usize {
+        let mut count = 0;
+        for entry in self
+            .commits
+            .iter_mut()
+            .filter(|entry| target.matches(entry))
+        {
+            if !entry.vetted_by.iter().any(|vetter| vetter == name) {
+                entry.vetted_by.push(name.to_string());
+            }
+            entry.follow_up = None;
+            count += 1;
+        }
+
+        count
+    }
+
+    /// Record that the entries matching `target` need more attention before they can be
+    /// vetted.
+    ///
+    /// Returns the number of matching entries.
+    pub fn flag_for_follow_up(&mut self, target: &Target, note: &str) -> usize {
+        let mut count = 0;
+        for entry in self
+            .commits
+            .iter_mut()
+            .filter(|entry| target.matches(entry))
+        {
+            entry.follow_up = Some(note.to_string());
+            count += 1;
+        }
+
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit_report::read_tsv;
+
+    const HASH_A: &str = "e36c080ef8c117278533ea43f84c90f9bed7f882";
+    const HASH_B: &str = "42b48ecb9ff6287ceef0c4203ffe672dffec4f2c";
+
+    #[test]
+    fn import_does_not_duplicate_commits() {
+        let mut ledger = Ledger::default();
+        ledger.commits.push(Commit {
+            pull_request: Some(3446),
+            hash: HASH_A.to_string(),
+            author: "crowlKats".to_string(),
+            vetted_by: vec!["nical".to_string()],
+            ..Commit::default()
+        });
+
+        let text = format!(
+            "\t{HASH_A}\tsomeone-else\t\tteoxoy\tteoxoy\n\
+             3447\t{HASH_B}\tElabajaba\tcwfitzgerald\tcwfitzgerald\t\n\
+             3447\t{HASH_B}\tElabajaba\tcwfitzgerald\tcwfitzgerald\tjimb\n"
+        );
+        let added = ledger.import(read_tsv(text.as_bytes()).unwrap());
+
+        assert_eq!(added, 1);
+        assert_eq!(ledger.commits.len(), 2);
+
+        // The data in the ledger takes precedence, vetters are combined.
+        let a = &ledger.commits[0];
+        assert_eq!(a.pull_request, Some(3446));
+        assert_eq!(a.author, "crowlKats");
+        assert_eq!(a.merger.as_deref(), Some("teoxoy"));
+        assert_eq!(a.vetted_by, vec!["nical", "teoxoy"]);
+
+        let b = &ledger.commits[1];
+        assert_eq!(b.hash, HASH_B);
+        assert_eq!(b.vetted_by, vec!["jimb"]);
+
+        // Importing the same rows again changes nothing.
+        assert_eq!(ledger.import(read_tsv(text.as_bytes()).unwrap()), 0);
+        assert_eq!(ledger.commits.len(), 2);
+    }
+
+    #[test]
+    fn merge_recomputes_policy_vetters() {
+        let mut ledger = Ledger::default();
+        ledger.commits.push(Commit {
+            pull_request: Some(3446),
+            hash: HASH_A.to_string(),
+            vetted_by: vec!["jimb".to_string()],
+            policy_vetted_by: vec!["teoxoy".to_string()],
+            vetting_reasons: vec!["merged by trusted reviewer teoxoy".to_string()],
+            ..Commit::default()
+        });
+
+        // teoxoy is no longer trusted when the commit is audited again.
+        let mut commits = vec![Commit {
+            pull_request: Some(3446),
+            hash: HASH_A.to_string(),
+            ..Commit::default()
+        }];
+        assert_eq!(ledger.merge(&mut commits), 0);
+
+        let entry = &ledger.commits[0];
+        assert_eq!(entry.vetted_by, vec!["jimb"]);
+        assert!(entry.policy_vetted_by.is_empty());
+        assert!(entry.vetting_reasons.is_empty());
+        assert_eq!(commits[0].vetters(), vec!["jimb"]);
+    }
+}
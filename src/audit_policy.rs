@@ -0,0 +1,264 @@
+use serde_derive::{Deserialize, Serialize};
+
+/// Decides which commits are considered vetted without a manual audit.
+///
+/// Configured in the `[wgpu.trust-policy]` section of the config file. By default, a pull request
+/// needs an approving review from a trusted reviewer who is not its author, made on its last
+/// commit. Trusting the author or the merger brings back the original, more permissive behavior.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct TrustPolicy {
+    /// Consider pull requests authored by a trusted reviewer as vetted.
+    pub trust_authors: bool,
+    /// Consider pull requests merged by a trusted reviewer as vetted.
+    pub trust_mergers: bool,
+    /// Ignore approvals that were made on an older commit than the last one pushed to the
+    /// pull request.
+    pub ignore_stale_approvals: bool,
+    /// Accounts that are automated rather than people.
+    pub bots: Vec<String>,
+    /// How to treat pull requests authored by one of the `bots`.
+    pub bot_policy: BotPolicy,
+}
+
+impl Default for TrustPolicy {
+    fn default() -> Self {
+        TrustPolicy {
+            trust_authors: false,
+            trust_mergers: false,
+            ignore_stale_approvals: true,
+            bots: vec!["dependabot[bot]".into(), "renovate[bot]".into()],
+            bot_policy: BotPolicy::default(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum BotPolicy {
+    /// Only an approving review from a trusted reviewer vets a bot's pull request, the merger is
+    /// not enough.
+    #[default]
+    RequireReview,
+    /// Pull requests authored by bots are considered vetted.
+    Trusted,
+    /// Pull requests authored by bots always have to be audited manually.
+    Manual,
+}
+
+/// An approving review on a pull request.
+#[derive(Clone, Debug)]
+pub struct Approval {
+    pub reviewer: String,
+    /// The commit the review was made on.
+    pub commit_id: Option<String>,
+    /// Whether the review was dismissed, in which case it does not count.
+    pub dismissed: bool,
+}
+
+/// The information about a pull request that the policy looks at.
+pub struct PullRequestInfo<'a> {
+    pub author: &'a str,
+    /// The last commit pushed to the pull request.
+    pub head_sha: Option<&'a str>,
+    pub approvals: &'a [Approval],
+    pub merger: Option<&'a str>,
+}
+
+/// A person (or bot) vouching for a pull request, and why they are trusted to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Vetter {
+    pub name: String,
+    pub reason: String,
+}
+
+impl TrustPolicy {
+    pub fn is_bot(&self, name: &str) -> bool {
+        self.bots.iter().any(|bot| bot == name)
+    }
+
+    /// Returns the list of vetters of a pull request. An empty list means that the pull request
+    /// has to be audited manually.
+    pub fn vetters(&self, trusted: &[String], pr: &PullRequestInfo) -> Vec<Vetter> {
+        let mut vetters: Vec<Vetter> = Vec::new();
+        let mut add = |name: &str, reason: String| {
+            if !vetters.iter().any(|vetter| vetter.name == name) {
+                vetters.push(Vetter {
+                    name: name.to_string(),
+                    reason,
+                });
+            }
+        };
+        let is_trusted = |name: &str| trusted.iter().any(|trusted| trusted == name);
+
+        let author_is_bot = self.is_bot(pr.author);
+        if author_is_bot {
+            match self.bot_policy {
+                BotPolicy::Manual => return Vec::new(),
+                BotPolicy::Trusted => add(pr.author, format!("authored by bot {}", pr.author)),
+                BotPolicy::RequireReview => {}
+            }
+        } else if self.trust_authors && is_trusted(pr.author) {
+            add(
+                pr.author,
+                format!("authored by trusted reviewer {}", pr.author),
+            );
+        }
+
+        for approval in pr.approvals {
+            let reviewer = approval.reviewer.as_str();
+            if approval.dismissed || reviewer == pr.author || !is_trusted(reviewer) {
+                continue;
+            }
+
+            let stale = match (pr.head_sha, &approval.commit_id) {
+                (Some(head), Some(commit)) => head != commit,
+                _ => false,
+            };
+            if stale && self.ignore_stale_approvals {
+                continue;
+            }
+
+            add(reviewer, format!("approved by trusted reviewer {reviewer}"));
+        }
+
+        if let Some(merger) = pr.merger {
+            if self.trust_mergers && !author_is_bot && is_trusted(merger) {
+                add(merger, format!("merged by trusted reviewer {merger}"));
+            }
+        }
+
+        vetters
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AUTHOR: &str = "crowlKats";
+    const REVIEWER: &str = "teoxoy";
+    const HEAD: &str = "e36c080ef8c117278533ea43f84c90f9bed7f882";
+    const OLD: &str = "42b48ecb9ff6287ceef0c4203ffe672dffec4f2c";
+
+    fn trusted() -> Vec<String> {
+        vec![AUTHOR.to_string(), REVIEWER.to_string()]
+    }
+
+    fn approval(reviewer: &str, commit_id: &str) -> Approval {
+        Approval {
+            reviewer: reviewer.to_string(),
+            commit_id: Some(commit_id.to_string()),
+            dismissed: false,
+        }
+    }
+
+    fn vetters(
+        policy: &TrustPolicy,
+        author: &str,
+        approvals: &[Approval],
+        merger: Option<&str>,
+    ) -> Vec<String> {
+        let pr = PullRequestInfo {
+            author,
+            head_sha: Some(HEAD),
+            approvals,
+            merger,
+        };
+        policy
+            .vetters(&trusted(), &pr)
+            .into_iter()
+            .map(|vetter| vetter.name)
+            .collect()
+    }
+
+    #[test]
+    fn approval_from_trusted_reviewer() {
+        let policy = TrustPolicy::default();
+        let pr = PullRequestInfo {
+            author: "someone",
+            head_sha: Some(HEAD),
+            approvals: &[approval(REVIEWER, HEAD)],
+            merger: None,
+        };
+        assert_eq!(
+            policy.vetters(&trusted(), &pr),
+            [Vetter {
+                name: REVIEWER.to_string(),
+                reason: format!("approved by trusted reviewer {REVIEWER}"),
+            }]
+        );
+
+        // Approvals from people who are not trusted do not count.
+        assert!(vetters(&policy, AUTHOR, &[approval("someone", HEAD)], None).is_empty());
+    }
+
+    #[test]
+    fn author_only() {
+        let mut policy = TrustPolicy::default();
+        assert!(vetters(&policy, AUTHOR, &[], None).is_empty());
+
+        policy.trust_authors = true;
+        assert_eq!(vetters(&policy, AUTHOR, &[], None), [AUTHOR]);
+    }
+
+    #[test]
+    fn merger_only() {
+        let mut policy = TrustPolicy::default();
+        assert!(vetters(&policy, "someone", &[], Some(REVIEWER)).is_empty());
+
+        policy.trust_mergers = true;
+        assert_eq!(vetters(&policy, "someone", &[], Some(REVIEWER)), [REVIEWER]);
+    }
+
+    #[test]
+    fn self_approval() {
+        let policy = TrustPolicy::default();
+        assert!(vetters(&policy, AUTHOR, &[approval(AUTHOR, HEAD)], None).is_empty());
+    }
+
+    #[test]
+    fn dismissed_approval() {
+        let policy = TrustPolicy::default();
+        let dismissed = Approval {
+            dismissed: true,
+            ..approval(REVIEWER, HEAD)
+        };
+        assert!(vetters(&policy, "someone", &[dismissed], None).is_empty());
+    }
+
+    #[test]
+    fn stale_approval() {
+        let mut policy = TrustPolicy::default();
+        assert!(vetters(&policy, "someone", &[approval(REVIEWER, OLD)], None).is_empty());
+
+        policy.ignore_stale_approvals = false;
+        assert_eq!(
+            vetters(&policy, "someone", &[approval(REVIEWER, OLD)], None),
+            [REVIEWER]
+        );
+    }
+
+    #[test]
+    fn bot_policies() {
+        let bot = "dependabot[bot]";
+        let mut policy = TrustPolicy {
+            trust_mergers: true,
+            ..TrustPolicy::default()
+        };
+
+        // The merger is not enough for a bot's pull request.
+        policy.bot_policy = BotPolicy::RequireReview;
+        assert!(vetters(&policy, bot, &[], Some(REVIEWER)).is_empty());
+        assert_eq!(
+            vetters(&policy, bot, &[approval(REVIEWER, HEAD)], Some(REVIEWER)),
+            [REVIEWER]
+        );
+
+        policy.bot_policy = BotPolicy::Trusted;
+        assert_eq!(vetters(&policy, bot, &[], None), [bot]);
+
+        policy.bot_policy = BotPolicy::Manual;
+        assert!(vetters(&policy, bot, &[approval(REVIEWER, HEAD)], Some(REVIEWER)).is_empty());
+    }
+}
@@ -0,0 +1,493 @@
+use crate::audit::Commit;
+use clap::ValueEnum;
+use serde_derive::Serialize;
+use std::io::{self, BufRead, Write};
+
+/// The version of the JSON report layout, bumped whenever fields are renamed or removed.
+const JSON_SCHEMA_VERSION: u32 = 1;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Tab separated values in the column order of the `wgpu-vet` spreadsheet.
+    #[default]
+    Tsv,
+    /// Comma separated values, with a header row and the pull request titles.
+    Csv,
+    /// A JSON document meant to be consumed by other tools.
+    Json,
+    /// A Markdown table that can be pasted into a bug.
+    Markdown,
+    /// A standalone HTML page.
+    Html,
+}
+
+impl OutputFormat {
+    /// Formats meant to be parsed by other tools rather than read.
+    pub fn is_machine_readable(self) -> bool {
+        matches!(
+            self,
+            OutputFormat::Tsv | OutputFormat::Csv | OutputFormat::Json
+        )
+    }
+}
+
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    schema_version: u32,
+    repository: &'a str,
+    commits: &'a [Commit],
+}
+
+pub fn write_report(
+    commits: &[Commit],
+    repository: &str,
+    format: OutputFormat,
+    writer: &mut dyn Write,
+) -> io::Result<()> {
+    match format {
+        OutputFormat::Tsv => write_tsv(commits, writer),
+        OutputFormat::Csv => write_csv(commits, writer),
+        OutputFormat::Json => write_json(commits, repository, writer),
+        OutputFormat::Markdown => write_markdown(commits, repository, writer),
+        OutputFormat::Html => write_html(commits, repository, writer),
+    }
+}
+
+fn pull_request_string(item: &Commit) -> String {
+    item.pull_request
+        .map(|num| format!("{num}"))
+        .unwrap_or_default()
+}
+
+pub fn write_tsv(items: &[Commit], writer: &mut dyn Write) -> io::Result<()> {
+    for item in items {
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            pull_request_string(item),
+            item.hash,
+            item.author,
+            item.reviewers.join(","),
+            item.merger.clone().unwrap_or_default(),
+            item.vetters().join(","),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Split a tab separated line. Fields can be quoted the way spreadsheets export them, in which
+/// case they may contain tabs and `""` stands for a quote.
+fn tsv_fields(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        let mut field = String::new();
+        let mut quoted = chars.next_if_eq(&'"').is_some();
+        let mut end_of_line = true;
+        while let Some(c) = chars.next() {
+            if quoted && c == '"' {
+                if chars.next_if_eq(&'"').is_some() {
+                    field.push('"');
+                } else {
+                    quoted = false;
+                }
+            } else if !quoted && c == '\t' {
+                end_of_line = false;
+                break;
+            } else {
+                field.push(c);
+            }
+        }
+
+        fields.push(field.trim().to_string());
+        if end_of_line {
+            return fields;
+        }
+    }
+}
+
+/// Parse rows in the format written by `write_tsv`, which is also the format the `wgpu-vet`
+/// spreadsheet exports to: `pull request`, `commit`, `author`, `reviewers`, `merger`, `vetted by`.
+///
+/// A header row and columns beyond the sixth are ignored, missing trailing columns are empty.
+pub fn read_tsv(reader: impl BufRead) -> io::Result<Vec<Commit>> {
+    fn names(column: Option<&String>) -> Vec<String> {
+        column
+            .map(String::as_str)
+            .unwrap_or("")
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+
+    let mut commits = Vec::new();
+    for (idx, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let columns = tsv_fields(&line);
+        let column = |idx: usize| columns.get(idx).map(String::as_str).unwrap_or("");
+        let pull_request = column(0);
+        let hash = column(1);
+
+        if idx == 0 && pull_request.eq_ignore_ascii_case("pull request") {
+            continue;
+        }
+
+        if hash.len() < 7 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Line {}: expected a commit hash in the second column, got {hash:?}",
+                    idx + 1
+                ),
+            ));
+        }
+
+        let pull_request = match pull_request.trim_start_matches('#') {
+            "" => None,
+            num => Some(num.parse().map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "Line {}: invalid pull request number {pull_request:?}",
+                        idx + 1
+                    ),
+                )
+            })?),
+        };
+
+        let merger = Some(column(4).to_string()).filter(|name| !name.is_empty());
+
+        commits.push(Commit {
+            pull_request,
+            hash: hash.to_lowercase(),
+            author: column(2).to_string(),
+            reviewers: names(columns.get(3)),
+            merger,
+            vetted_by: names(columns.get(5)),
+            ..Commit::default()
+        });
+    }
+
+    Ok(commits)
+}
+
+/// Quote a CSV field if it contains a separator, a quote or a line break.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\t', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn write_csv(items: &[Commit], writer: &mut dyn Write) -> io::Result<()> {
+    writeln!(
+        writer,
+        "pull request,title,commit,author,reviewers,merger,vetted by,vetting reasons,risk score,risk flags,verified"
+    )?;
+
+    for item in items {
+        let fields = [
+            pull_request_string(item),
+            item.pull_request_title.clone(),
+            item.hash.clone(),
+            item.author.clone(),
+            item.reviewers.join(","),
+            item.merger.clone().unwrap_or_default(),
+            item.vetters().join(","),
+            item.vetting_reasons.join("; "),
+            format!("{}", item.risk.score),
+            item.risk.flags.join("; "),
+            match item.identity.verified {
+                Some(true) => "yes".to_string(),
+                Some(false) => "no".to_string(),
+                None => "unknown".to_string(),
+            },
+        ];
+
+        let fields: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        writeln!(writer, "{}", fields.join(","))?;
+    }
+
+    Ok(())
+}
+
+fn write_json(items: &[Commit], repository: &str, writer: &mut dyn Write) -> io::Result<()> {
+    let report = JsonReport {
+        schema_version: JSON_SCHEMA_VERSION,
+        repository,
+        commits: items,
+    };
+
+    serde_json::to_writer_pretty(&mut *writer, &report)?;
+    writeln!(writer)?;
+
+    Ok(())
+}
+
+/// The risk score followed by the flags, or nothing if the commit was not flagged.
+fn risk_summary(item: &Commit) -> String {
+    if item.risk.score == 0 {
+        return String::new();
+    }
+
+    format!("{} ({})", item.risk.score, item.risk.flags.join("; "))
+}
+
+/// Escape the text of a table cell: pipes would end the cell, backticks could start a code span
+/// that swallows the following cells, and line breaks would end the row.
+fn markdown_escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('|', "\\|")
+        .replace('`', "\\`")
+        .replace(['\r', '\n'], " ")
+}
+
+fn write_markdown(items: &[Commit], repository: &str, writer: &mut dyn Write) -> io::Result<()> {
+    writeln!(
+        writer,
+        "| Pull request | Title | Commit | Author | Reviewers | Merger | Vetted by | Vetting reasons | Risk |"
+    )?;
+    writeln!(writer, "|---|---|---|---|---|---|---|---|---|")?;
+
+    for item in items {
+        let pull_request = item
+            .pull_request
+            .map(|num| format!("[#{num}]({repository}/pull/{num})"))
+            .unwrap_or_else(|| "(none)".to_string());
+        let short_hash = &item.hash[..item.hash.len().min(10)];
+
+        writeln!(
+            writer,
+            "| {} | {} | [{}]({repository}/commit/{}) | {} | {} | {} | {} | {} | {} |",
+            pull_request,
+            markdown_escape(&item.pull_request_title),
+            short_hash,
+            item.hash,
+            markdown_escape(&item.author),
+            markdown_escape(&item.reviewers.join(", ")),
+            markdown_escape(&item.merger.clone().unwrap_or_default()),
+            markdown_escape(&item.vetters().join(", ")),
+            markdown_escape(&item.vetting_reasons.join("; ")),
+            markdown_escape(&risk_summary(item)),
+        )?;
+    }
+
+    Ok(())
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn write_html(items: &[Commit], repository: &str, writer: &mut dyn Write) -> io::Result<()> {
+    writeln!(writer, "<!DOCTYPE html>")?;
+    writeln!(writer, "<html>")?;
+    writeln!(
+        writer,
+        "<head><meta charset=\"utf-8\"><title>wgpu audit</title></head>"
+    )?;
+    writeln!(writer, "<body>")?;
+    writeln!(writer, "<table>")?;
+    writeln!(
+        writer,
+        "<tr><th>Pull request</th><th>Title</th><th>Commit</th><th>Author</th><th>Reviewers</th><th>Merger</th><th>Vetted by</th><th>Vetting reasons</th><th>Risk</th></tr>"
+    )?;
+
+    for item in items {
+        let pull_request = item
+            .pull_request
+            .map(|num| format!("<a href=\"{repository}/pull/{num}\">#{num}</a>"))
+            .unwrap_or_else(|| "(none)".to_string());
+
+        writeln!(
+            writer,
+            "<tr><td>{}</td><td>{}</td><td><a href=\"{repository}/commit/{}\"><code>{}</code></a></td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            pull_request,
+            html_escape(&item.pull_request_title),
+            item.hash,
+            item.hash,
+            html_escape(&item.author),
+            html_escape(&item.reviewers.join(", ")),
+            html_escape(&item.merger.clone().unwrap_or_default()),
+            html_escape(&item.vetters().join(", ")),
+            html_escape(&item.vetting_reasons.join("; ")),
+            html_escape(&risk_summary(item)),
+        )?;
+    }
+
+    writeln!(writer, "</table>")?;
+    writeln!(writer, "</body>")?;
+    writeln!(writer, "</html>")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASH_A: &str = "e36c080ef8c117278533ea43f84c90f9bed7f882";
+    const HASH_B: &str = "42b48ecb9ff6287ceef0c4203ffe672dffec4f2c";
+    const REPOSITORY: &str = "https://github.com/gfx-rs/wgpu";
+
+    /// Commits with the characters that need quoting or escaping in every format.
+    fn tricky_commits() -> Vec<Commit> {
+        vec![
+            Commit {
+                pull_request: Some(3446),
+                pull_request_title: "Fix \"quotes\", commas,\ttabs\nand | pipes in `code`"
+                    .to_string(),
+                hash: HASH_A.to_string(),
+                author: "crowl|Kats".to_string(),
+                reviewers: vec!["teoxoy".to_string(), "`nical`".to_string()],
+                merger: Some("a|b".to_string()),
+                policy_vetted_by: vec!["teoxoy".to_string()],
+                vetting_reasons: vec!["approved by trusted reviewer teoxoy".to_string()],
+                ..Commit::default()
+            },
+            Commit {
+                hash: HASH_B.to_string(),
+                ..Commit::default()
+            },
+        ]
+    }
+
+    fn report(commits: &[Commit], format: OutputFormat) -> String {
+        let mut output = Vec::new();
+        write_report(commits, REPOSITORY, format, &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    /// Parse CSV the way spreadsheets do: quoted fields may contain separators and line breaks,
+    /// and `""` stands for a quote.
+    fn parse_csv(text: &str) -> Vec<Vec<String>> {
+        let mut rows = Vec::new();
+        let mut row = Vec::new();
+        let mut field = String::new();
+        let mut quoted = false;
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' if quoted => {
+                    if chars.next_if_eq(&'"').is_some() {
+                        field.push('"');
+                    } else {
+                        quoted = false;
+                    }
+                }
+                '"' if field.is_empty() => quoted = true,
+                ',' if !quoted => row.push(std::mem::take(&mut field)),
+                '\n' if !quoted => {
+                    row.push(std::mem::take(&mut field));
+                    rows.push(std::mem::take(&mut row));
+                }
+                c => field.push(c),
+            }
+        }
+
+        rows
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        let commits = tricky_commits();
+        let rows = parse_csv(&report(&commits, OutputFormat::Csv));
+
+        assert_eq!(rows.len(), 3);
+        assert!(rows.iter().all(|row| row.len() == rows[0].len()));
+        assert_eq!(rows[0][..3], ["pull request", "title", "commit"]);
+
+        let row = &rows[1];
+        assert_eq!(row[0], "3446");
+        assert_eq!(row[1], commits[0].pull_request_title);
+        assert_eq!(row[2], HASH_A);
+        assert_eq!(row[3], "crowl|Kats");
+        assert_eq!(row[4], "teoxoy,`nical`");
+        assert_eq!(row[5], "a|b");
+        assert_eq!(row[6], "teoxoy");
+
+        assert_eq!(rows[2][0], "");
+        assert_eq!(rows[2][2], HASH_B);
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let commits = tricky_commits();
+        let json: serde_json::Value =
+            serde_json::from_str(&report(&commits, OutputFormat::Json)).unwrap();
+
+        assert_eq!(json["schema_version"], JSON_SCHEMA_VERSION);
+        assert_eq!(json["repository"], REPOSITORY);
+
+        let parsed: Vec<Commit> = serde_json::from_value(json["commits"].clone()).unwrap();
+        assert_eq!(parsed.len(), commits.len());
+        for (parsed, commit) in parsed.iter().zip(&commits) {
+            assert_eq!(parsed.pull_request, commit.pull_request);
+            assert_eq!(parsed.pull_request_title, commit.pull_request_title);
+            assert_eq!(parsed.hash, commit.hash);
+            assert_eq!(parsed.author, commit.author);
+            assert_eq!(parsed.reviewers, commit.reviewers);
+            assert_eq!(parsed.merger, commit.merger);
+            assert_eq!(parsed.vetters(), commit.vetters());
+            assert_eq!(parsed.vetting_reasons, commit.vetting_reasons);
+        }
+    }
+
+    #[test]
+    fn markdown_cells_are_escaped() {
+        let markdown = report(&tricky_commits(), OutputFormat::Markdown);
+        let lines: Vec<&str> = markdown.lines().collect();
+        assert_eq!(lines.len(), 4);
+
+        // Splitting on the pipes that are not escaped gives the same number of cells as the
+        // header.
+        let cells = |line: &str| line.replace("\\|", "").matches('|').count();
+        assert!(lines.iter().all(|line| cells(line) == cells(lines[0])));
+        assert!(lines[2].contains("| crowl\\|Kats | teoxoy, \\`nical\\` | a\\|b |"));
+    }
+
+    #[test]
+    fn read_tsv_quoted_fields() {
+        let text = format!(
+            "pull request\tcommit\tauthor\treviewers\tmerger\tvetted by\n\
+             #3446\t{HASH_A}\t\"crowl\"\"Kats\"\t\"teoxoy,\tnical\"\tteoxoy\tteoxoy\n"
+        );
+        let rows = read_tsv(text.as_bytes()).unwrap();
+
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].pull_request, Some(3446));
+        assert_eq!(rows[0].hash, HASH_A);
+        assert_eq!(rows[0].author, "crowl\"Kats");
+        assert_eq!(rows[0].reviewers, vec!["teoxoy", "nical"]);
+        assert_eq!(rows[0].merger.as_deref(), Some("teoxoy"));
+        assert_eq!(rows[0].vetted_by, vec!["teoxoy"]);
+    }
+
+    #[test]
+    fn read_tsv_missing_columns() {
+        let text = format!("3447\t{HASH_B}\tElabajaba\n\t{HASH_A}\n");
+        let rows = read_tsv(text.as_bytes()).unwrap();
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].author, "Elabajaba");
+        assert!(rows[0].reviewers.is_empty());
+        assert_eq!(rows[0].merger, None);
+        assert!(rows[0].vetted_by.is_empty());
+        assert_eq!(rows[1].pull_request, None);
+        assert_eq!(rows[1].hash, HASH_A);
+
+        assert!(read_tsv("3447\n".as_bytes()).is_err());
+        assert!(read_tsv("abc\tnot-a-hash\n".as_bytes()).is_err());
+    }
+}
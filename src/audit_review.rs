@@ -0,0 +1,92 @@
+use crate::{
+    audit::{print_audit_item, Commit},
+    audit_ledger::{Ledger, Target},
+    helpers::prompt,
+    shell,
+};
+use std::{io, path::Path};
+
+/// Walk the auditor through `entries` one commit at a time, showing the pull request and the
+/// files the commit touches, and record the decisions in the ledger.
+///
+/// The ledger is saved after each decision so that the review can be interrupted at any time.
+pub fn review(
+    repo: &Path,
+    ledger: &mut Ledger,
+    ledger_path: &Path,
+    entries: &[Commit],
+    name: &str,
+) -> io::Result<()> {
+    let mut hashes: Vec<&str> = Vec::new();
+    for entry in entries {
+        if !hashes.contains(&entry.hash.as_str()) {
+            hashes.push(&entry.hash);
+        }
+    }
+
+    if hashes.is_empty() {
+        println!("Nothing to review, all commits are vetted.");
+        return Ok(());
+    }
+
+    let (mut vetted, mut follow_up) = (0, 0);
+    'commits: for (idx, hash) in hashes.iter().enumerate() {
+        println!("\n# Commit {} of {}\n", idx + 1, hashes.len());
+        for entry in entries.iter().filter(|entry| entry.hash == *hash) {
+            print_audit_item(entry);
+            if !entry.reviewers.is_empty() {
+                println!("   Reviewers: {}", entry.reviewers.join(", "));
+            }
+            if let Some(merger) = &entry.merger {
+                println!("   Merged by: {merger}");
+            }
+        }
+        println!();
+        shell(
+            repo,
+            "git",
+            &[
+                "--no-pager",
+                "show",
+                "--stat",
+                "--format=%an <%ae>%n%s",
+                hash,
+            ],
+        )?;
+
+        let target = Target::Commit(hash.to_string());
+        loop {
+            let Some(answer) = prompt("\n[v]etted, [f]ollow-up, [d]iff, [s]kip, [q]uit? ")? else {
+                break 'commits;
+            };
+
+            match answer.as_str() {
+                "v" => {
+                    ledger.mark(&target, name);
+                    ledger.save(ledger_path)?;
+                    vetted += 1;
+                    break;
+                }
+                "f" => {
+                    let note = prompt("What needs to be followed up? ")?.unwrap_or_default();
+                    ledger.flag_for_follow_up(&target, &format!("{note} ({name})"));
+                    ledger.save(ledger_path)?;
+                    follow_up += 1;
+                    break;
+                }
+                "d" => {
+                    shell(repo, "git", &["show", hash])?;
+                }
+                "s" => break,
+                "q" => break 'commits,
+                _ => println!("Unknown answer {answer:?}"),
+            }
+        }
+    }
+
+    println!(
+        "\nMarked {vetted} commits as vetted and {follow_up} as needing a follow-up in {ledger_path:?}"
+    );
+
+    Ok(())
+}
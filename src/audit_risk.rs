@@ -0,0 +1,184 @@
+use crate::read_shell;
+use serde_derive::{Deserialize, Serialize};
+use std::{collections::BTreeSet, path::Path};
+
+/// `wgpu-hal` backends, changes in these directories interact directly with the platform APIs.
+const HAL_BACKENDS: &[&str] = &["vulkan", "metal", "dx12", "dx11", "gles"];
+
+/// The changes made to a file by a commit.
+#[derive(Clone, Debug, Default)]
+pub struct FileDiff {
+    pub path: String,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+/// Parse the output of `git diff --unified=0`.
+pub fn parse_diff(text: &str) -> Vec<FileDiff> {
+    let mut files: Vec<FileDiff> = Vec::new();
+    let mut in_hunk = false;
+
+    for line in text.lines() {
+        if let Some(paths) = line.strip_prefix("diff --git ") {
+            // "a/<path> b/<path>", the new path is the one that matters.
+            let path = paths
+                .rsplit_once(" b/")
+                .map(|(_, path)| path)
+                .unwrap_or(paths);
+            files.push(FileDiff {
+                path: path.to_string(),
+                ..FileDiff::default()
+            });
+            in_hunk = false;
+            continue;
+        }
+
+        let Some(file) = files.last_mut() else {
+            continue;
+        };
+
+        // Skip the file header ("---", "+++", mode changes, etc.) until the first hunk.
+        if line.starts_with("@@") {
+            in_hunk = true;
+            continue;
+        }
+        if !in_hunk {
+            continue;
+        }
+
+        if let Some(added) = line.strip_prefix('+') {
+            file.added.push(added.to_string());
+        } else if let Some(removed) = line.strip_prefix('-') {
+            file.removed.push(removed.to_string());
+        }
+    }
+
+    files
+}
+
+/// The changes made by a commit relative to its first parent.
+pub fn commit_diff(repo: &Path, hash: &str) -> Vec<FileDiff> {
+    let output = read_shell(
+        repo,
+        "git",
+        &[
+            "diff",
+            "--unified=0",
+            "--no-color",
+            "--no-ext-diff",
+            &format!("{hash}^"),
+            hash,
+        ],
+    );
+
+    parse_diff(&output.stdout)
+}
+
+/// Whether the code part of a line of Rust (ignoring line comments) contains the `unsafe` keyword.
+pub fn contains_unsafe_keyword(line: &str) -> bool {
+    let code = line.split("//").next().unwrap_or("");
+    code.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .any(|word| word == "unsafe")
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Risk {
+    /// Higher means the commit should be looked at first.
+    pub score: u32,
+    /// Human readable description of what makes the commit risky.
+    pub flags: Vec<String>,
+}
+
+impl Risk {
+    fn flag(&mut self, weight: u32, flag: String) {
+        self.score += weight;
+        self.flags.push(flag);
+    }
+}
+
+/// Dependency names declared in the text of a `Cargo.toml` file.
+fn dependencies(cargo_toml: &str) -> BTreeSet<String> {
+    fn collect(table: &toml::value::Table, deps: &mut BTreeSet<String>) {
+        for (key, value) in table {
+            match (key.as_str(), value) {
+                (
+                    "dependencies" | "dev-dependencies" | "build-dependencies",
+                    toml::Value::Table(list),
+                ) => {
+                    deps.extend(list.keys().cloned());
+                }
+                ("workspace", toml::Value::Table(workspace)) => collect(workspace, deps),
+                ("target", toml::Value::Table(targets)) => {
+                    for target in targets.values() {
+                        if let toml::Value::Table(target) = target {
+                            collect(target, deps);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut deps = BTreeSet::new();
+    if let Ok(toml::Value::Table(table)) = cargo_toml.parse::<toml::Value>() {
+        collect(&table, &mut deps);
+    }
+
+    deps
+}
+
+fn file_at_revision(repo: &Path, rev: &str, path: &str) -> String {
+    read_shell(repo, "git", &["show", &format!("{rev}:{path}")]).stdout
+}
+
+/// Look at the changes of a commit (`diff`, obtained with `commit_diff`) and flag the ones that
+/// deserve extra attention during the audit.
+pub fn assess_commit(repo: &Path, hash: &str, diff: &[FileDiff]) -> Risk {
+    let mut risk = Risk::default();
+    let mut hal_backends = BTreeSet::new();
+
+    for file in diff {
+        let path = file.path.as_str();
+        let file_name = path.rsplit('/').next().unwrap_or(path);
+
+        if path.ends_with(".rs") {
+            if file.added.iter().any(|line| contains_unsafe_keyword(line)) {
+                risk.flag(3, format!("adds unsafe code in {path}"));
+            }
+            if file.added.iter().any(|line| line.contains("proc_macro")) {
+                risk.flag(3, format!("proc-macro code in {path}"));
+            }
+        }
+
+        if file_name == "build.rs" {
+            risk.flag(3, format!("build script {path}"));
+        }
+
+        if file_name == "Cargo.toml" {
+            if file.added.iter().any(|line| line.contains("proc-macro")) {
+                risk.flag(3, format!("declares a proc-macro in {path}"));
+            }
+
+            let before = dependencies(&file_at_revision(repo, &format!("{hash}^"), path));
+            let after = dependencies(&file_at_revision(repo, hash, path));
+            for dep in after.difference(&before) {
+                risk.flag(4, format!("new dependency {dep} in {path}"));
+            }
+        }
+
+        if let Some(rest) = path.strip_prefix("wgpu-hal/src/") {
+            let dir = rest.split('/').next().unwrap_or("");
+            if HAL_BACKENDS.contains(&dir) {
+                hal_backends.insert(dir.to_string());
+            }
+        }
+    }
+
+    for backend in hal_backends {
+        risk.flag(2, format!("touches the wgpu-hal {backend} backend"));
+    }
+
+    risk
+}
@@ -0,0 +1,152 @@
+use crate::audit_risk::{commit_diff, contains_unsafe_keyword, FileDiff};
+use serde_derive::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt::Write, path::Path};
+
+/// The crates of the wgpu repository that unsafe code is tracked for, other paths are counted
+/// under "other".
+const CRATES: &[&str] = &["wgpu-core", "wgpu-hal", "wgpu-types", "naga"];
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UnsafeCounts {
+    pub blocks: u32,
+    pub fns: u32,
+    pub impls: u32,
+    pub traits: u32,
+}
+
+impl UnsafeCounts {
+    fn add(&mut self, other: &UnsafeCounts) {
+        self.blocks += other.blocks;
+        self.fns += other.fns;
+        self.impls += other.impls;
+        self.traits += other.traits;
+    }
+
+    fn is_empty(&self) -> bool {
+        *self == UnsafeCounts::default()
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UnsafeChurn {
+    pub added: UnsafeCounts,
+    pub removed: UnsafeCounts,
+}
+
+impl UnsafeChurn {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Unsafe churn keyed by crate name.
+pub type UnsafeChurnPerCrate = BTreeMap<String, UnsafeChurn>;
+
+/// Count the occurrences of the `unsafe` keyword in a line of Rust code, by kind.
+fn count_line(line: &str, counts: &mut UnsafeCounts) {
+    if !contains_unsafe_keyword(line) {
+        return;
+    }
+
+    let code = line.split("//").next().unwrap_or("");
+    let mut rest = code;
+    while let Some(idx) = rest.find("unsafe") {
+        let before = rest[..idx].chars().next_back();
+        let after = &rest[idx + "unsafe".len()..];
+        rest = after;
+
+        let is_word = |c: Option<char>| c.map(|c| c.is_alphanumeric() || c == '_').unwrap_or(false);
+        if is_word(before) || is_word(after.chars().next()) {
+            continue;
+        }
+
+        let after = after.trim_start();
+        if after.starts_with("fn") || after.starts_with("extern") {
+            counts.fns += 1;
+        } else if after.starts_with("impl") {
+            counts.impls += 1;
+        } else if after.starts_with("trait") {
+            counts.traits += 1;
+        } else {
+            counts.blocks += 1;
+        }
+    }
+}
+
+fn crate_for_path(path: &str) -> &'static str {
+    CRATES
+        .iter()
+        .find(|name| path.starts_with(&format!("{name}/")))
+        .copied()
+        .unwrap_or("other")
+}
+
+/// Count the unsafe blocks, functions, impls and traits added and removed by a diff.
+pub fn unsafe_churn(diff: &[FileDiff]) -> UnsafeChurnPerCrate {
+    let mut result = UnsafeChurnPerCrate::new();
+
+    for file in diff.iter().filter(|file| file.path.ends_with(".rs")) {
+        let mut churn = UnsafeChurn::default();
+        for line in &file.added {
+            count_line(line, &mut churn.added);
+        }
+        for line in &file.removed {
+            count_line(line, &mut churn.removed);
+        }
+
+        if !churn.is_empty() {
+            let entry = result
+                .entry(crate_for_path(&file.path).to_string())
+                .or_default();
+            entry.added.add(&churn.added);
+            entry.removed.add(&churn.removed);
+        }
+    }
+
+    result
+}
+
+/// Add `churn` into `total`.
+pub fn accumulate(total: &mut UnsafeChurnPerCrate, churn: &UnsafeChurnPerCrate) {
+    for (name, churn) in churn {
+        let entry = total.entry(name.clone()).or_default();
+        entry.added.add(&churn.added);
+        entry.removed.add(&churn.removed);
+    }
+}
+
+/// The summed unsafe churn of a list of commits in the local checkout.
+pub fn unsafe_churn_for_commits(repo: &Path, commits: &[String]) -> UnsafeChurnPerCrate {
+    let mut total = UnsafeChurnPerCrate::new();
+    for hash in commits {
+        accumulate(&mut total, &unsafe_churn(&commit_diff(repo, hash)));
+    }
+
+    total
+}
+
+/// A few lines of text with one line per crate, for example:
+///
+/// ```text
+/// wgpu-hal: blocks +3 -1, fns +1 -0, impls +0 -0, traits +0 -0
+/// ```
+pub fn format_unsafe_churn(churn: &UnsafeChurnPerCrate) -> String {
+    let mut text = String::new();
+    if churn.is_empty() {
+        text.push_str("No unsafe code added or removed.\n");
+        return text;
+    }
+
+    for (name, churn) in churn {
+        let (a, r) = (&churn.added, &churn.removed);
+        let _ = writeln!(
+            text,
+            "{name}: blocks +{} -{}, fns +{} -{}, impls +{} -{}, traits +{} -{}",
+            a.blocks, r.blocks, a.fns, r.fns, a.impls, r.impls, a.traits, r.traits,
+        );
+    }
+
+    text
+}
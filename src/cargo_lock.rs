@@ -54,3 +54,53 @@ pub fn find_version(name: &str, gecko_path: &Path) -> io::Result<Version> {
         git_hash: git_hash.unwrap(),
     })
 }
+
+/// A `[[package]]` entry of a `Cargo.lock` file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LockedPackage {
+    pub name: String,
+    pub version: String,
+    /// For example `registry+https://github.com/rust-lang/crates.io-index`, empty for the crates
+    /// of the workspace.
+    pub source: String,
+}
+
+impl LockedPackage {
+    pub fn is_from_registry(&self) -> bool {
+        self.source.starts_with("registry+")
+    }
+}
+
+/// Parse the packages of a `Cargo.lock` file.
+pub fn parse_packages(text: &str) -> io::Result<Vec<LockedPackage>> {
+    let value: toml::Value = text.parse().map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Failed to parse Cargo.lock: {e}"),
+        )
+    })?;
+
+    let field = |package: &toml::Value, key: &str| {
+        package
+            .get(key)
+            .and_then(|value| value.as_str())
+            .unwrap_or_default()
+            .to_string()
+    };
+
+    let mut packages = Vec::new();
+    for package in value
+        .get("package")
+        .and_then(|packages| packages.as_array())
+        .into_iter()
+        .flatten()
+    {
+        packages.push(LockedPackage {
+            name: field(package, "name"),
+            version: field(package, "version"),
+            source: field(package, "source"),
+        });
+    }
+
+    Ok(packages)
+}
@@ -0,0 +1,292 @@
+use crate::{audit::git_output, audit_risk::FileDiff};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+    io,
+    path::Path,
+};
+
+/// A line added to wgpu's `CHANGELOG.md`.
+#[derive(Clone, Debug)]
+pub struct ChangelogEntry {
+    /// The headings the entry is under, outermost first.
+    pub headings: Vec<String>,
+    pub text: String,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ChangeKind {
+    Breaking,
+    NewFeature,
+    BugFix,
+    Other,
+}
+
+impl ChangeKind {
+    pub fn title(self) -> &'static str {
+        match self {
+            ChangeKind::Breaking => "Breaking changes",
+            ChangeKind::NewFeature => "New features",
+            ChangeKind::BugFix => "Bug fixes",
+            ChangeKind::Other => "Other changes",
+        }
+    }
+}
+
+impl ChangelogEntry {
+    pub fn is_breaking(&self) -> bool {
+        self.kind() == ChangeKind::Breaking
+    }
+
+    /// Classify the entry based on the headings it is under, falling back to its text.
+    pub fn kind(&self) -> ChangeKind {
+        let headings: Vec<String> = self.headings.iter().map(|h| h.to_lowercase()).collect();
+        let in_heading = |needles: &[&str]| {
+            headings
+                .iter()
+                .any(|heading| needles.iter().any(|needle| heading.contains(needle)))
+        };
+
+        if in_heading(&["breaking"]) || self.text.to_lowercase().contains("breaking") {
+            ChangeKind::Breaking
+        } else if in_heading(&["bug", "fix"]) {
+            ChangeKind::BugFix
+        } else if in_heading(&["feature", "addition", "added", "new"]) {
+            ChangeKind::NewFeature
+        } else {
+            ChangeKind::Other
+        }
+    }
+
+    /// Whether the entry is about `wgpu-core`, whose API `gfx/wgpu_bindings` uses directly.
+    pub fn is_about_core(&self) -> bool {
+        self.text.contains("wgpu-core")
+            || self
+                .headings
+                .iter()
+                .any(|heading| heading.to_lowercase().contains("core"))
+    }
+
+    /// The pull request numbers referenced in the entry, typically as `[#1234](...)`.
+    pub fn pull_requests(&self) -> Vec<u64> {
+        let mut result = Vec::new();
+        for (idx, _) in self.text.match_indices('#') {
+            let digits: String = self.text[idx + 1..]
+                .chars()
+                .take_while(char::is_ascii_digit)
+                .collect();
+            if let Ok(num) = digits.parse() {
+                if !result.contains(&num) {
+                    result.push(num);
+                }
+            }
+        }
+
+        result
+    }
+}
+
+/// Parse the entries (list items) of a changelog in markdown, keeping track of the headings
+/// they are under.
+fn parse_changelog(text: &str) -> Vec<ChangelogEntry> {
+    let mut headings: Vec<(usize, String)> = Vec::new();
+    let mut entries = Vec::new();
+
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('#') {
+            let level = trimmed.chars().take_while(|c| *c == '#').count();
+            headings.retain(|(l, _)| *l < level);
+            headings.push((level, trimmed[level..].trim().to_string()));
+            continue;
+        }
+
+        if trimmed.starts_with("- ") || trimmed.starts_with("* ") {
+            entries.push(ChangelogEntry {
+                headings: headings.iter().map(|(_, h)| h.clone()).collect(),
+                text: trimmed[2..].trim().to_string(),
+            });
+        }
+    }
+
+    entries
+}
+
+/// The changelog entries that exist at revision `to` but not at revision `from`.
+pub fn added_changelog_entries(
+    repo: &Path,
+    from: &str,
+    to: &str,
+) -> io::Result<Vec<ChangelogEntry>> {
+    let read = |rev: &str| git_output(repo, &["show", &format!("{rev}:CHANGELOG.md")]);
+
+    let previous: BTreeSet<String> = parse_changelog(&read(from)?)
+        .into_iter()
+        .map(|entry| entry.text)
+        .collect();
+
+    Ok(parse_changelog(&read(to)?)
+        .into_iter()
+        .filter(|entry| !previous.contains(&entry.text))
+        .collect())
+}
+
+/// The parts of the wgpu repository touched by a diff. `wgpu-hal` changes are attributed to
+/// the backend they touch.
+pub fn crates_touched(diff: &[FileDiff]) -> BTreeSet<String> {
+    let mut crates = BTreeSet::new();
+    for file in diff {
+        let mut components = file.path.split('/');
+        let name = match components.next().unwrap_or("") {
+            "wgpu-hal" => match (components.next(), components.next()) {
+                (Some("src"), Some(dir)) if !dir.ends_with(".rs") => format!("wgpu-hal ({dir})"),
+                _ => "wgpu-hal".to_string(),
+            },
+            dir @ ("naga" | "wgpu-core" | "wgpu-types" | "wgpu") => dir.to_string(),
+            _ => "other".to_string(),
+        };
+        crates.insert(name);
+    }
+
+    crates
+}
+
+/// A merged pull request, as it appears in the changelog.
+#[derive(Clone, Debug)]
+pub struct ChangelogPullRequest {
+    pub number: u64,
+    pub title: String,
+    pub author: String,
+    pub labels: Vec<String>,
+    pub crates: BTreeSet<String>,
+}
+
+impl ChangelogPullRequest {
+    /// The changelog category, derived from the GitHub labels.
+    fn category(&self) -> &'static str {
+        let has = |needle: &str| {
+            self.labels
+                .iter()
+                .any(|label| label.to_lowercase().contains(needle))
+        };
+
+        if has("bug") {
+            "Bug fixes"
+        } else if has("enhancement") || has("feature") {
+            "New features"
+        } else if has("performance") {
+            "Performance"
+        } else if has("documentation") {
+            "Documentation"
+        } else {
+            "Other changes"
+        }
+    }
+}
+
+/// Render the changelog in markdown, with the breaking changes first and the pull requests
+/// grouped by crate and category.
+pub fn render_changelog(
+    pull_requests: &[ChangelogPullRequest],
+    entries: &[ChangelogEntry],
+    repository: &str,
+) -> String {
+    let breaking: BTreeSet<u64> = entries
+        .iter()
+        .filter(|entry| entry.is_breaking())
+        .flat_map(|entry| entry.pull_requests())
+        .collect();
+
+    let mut text = String::new();
+    let line = |text: &mut String, pr: &ChangelogPullRequest| {
+        let mut labels = String::new();
+        if !pr.labels.is_empty() {
+            labels = format!(" [{}]", pr.labels.join(", "));
+        }
+        let _ = writeln!(
+            text,
+            " * #{} {}{labels}\n   By {} in {repository}/pull/{}",
+            pr.number, pr.title, pr.author, pr.number
+        );
+    };
+
+    let _ = writeln!(text, "# Changelog\n");
+
+    let breaking_entries: Vec<&ChangelogEntry> =
+        entries.iter().filter(|entry| entry.is_breaking()).collect();
+    if !breaking_entries.is_empty() {
+        let _ = writeln!(text, "## Breaking changes\n");
+        for entry in breaking_entries {
+            let _ = writeln!(text, " * {}", entry.text);
+        }
+        for pr in pull_requests
+            .iter()
+            .filter(|pr| breaking.contains(&pr.number))
+        {
+            line(&mut text, pr);
+        }
+        let _ = writeln!(text);
+    }
+
+    let mut groups: BTreeMap<&str, BTreeMap<&str, Vec<&ChangelogPullRequest>>> = BTreeMap::new();
+    for pr in pull_requests {
+        if pr.crates.is_empty() {
+            groups
+                .entry("other")
+                .or_default()
+                .entry(pr.category())
+                .or_default()
+                .push(pr);
+        }
+        for name in &pr.crates {
+            groups
+                .entry(name)
+                .or_default()
+                .entry(pr.category())
+                .or_default()
+                .push(pr);
+        }
+    }
+
+    for (name, categories) in groups {
+        let _ = writeln!(text, "## {name}\n");
+        for (category, prs) in categories {
+            let _ = writeln!(text, "### {category}\n");
+            for pr in prs {
+                line(&mut text, pr);
+            }
+            let _ = writeln!(text);
+        }
+    }
+
+    text
+}
+
+/// A summary of the changelog entries by kind, highlighting the ones that are about `wgpu-core`.
+pub fn summarize_changelog(entries: &[ChangelogEntry]) -> String {
+    let mut text = String::new();
+    if entries.is_empty() {
+        let _ = writeln!(text, "No new entries in `CHANGELOG.md`.");
+        return text;
+    }
+
+    let mut kinds: BTreeMap<ChangeKind, Vec<&ChangelogEntry>> = BTreeMap::new();
+    for entry in entries {
+        kinds.entry(entry.kind()).or_default().push(entry);
+    }
+
+    for (kind, entries) in kinds {
+        let _ = writeln!(text, "## {} ({})\n", kind.title(), entries.len());
+        for entry in entries {
+            let core = if entry.is_about_core() {
+                " [wgpu-core]"
+            } else {
+                ""
+            };
+            let _ = writeln!(text, " * {}{core}", entry.text);
+        }
+        let _ = writeln!(text);
+    }
+
+    text
+}
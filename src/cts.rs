@@ -1,9 +1,19 @@
 use clap::Parser;
-use std::env::current_dir;
-use std::io;
-use std::path::PathBuf;
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
+use crate::cts_compare;
+use crate::cts_flaky;
+use crate::cts_metadata::{self, Preset};
+use crate::cts_queries;
+use crate::cts_runs::{self, RunManifest};
+use crate::helpers::prompt;
+use crate::moz_yaml;
+use crate::taskcluster::{self, Taskcluster};
+use crate::wgpu_update;
+use crate::wptreport::{self, ReportSet};
 use crate::Config;
 
 use crate::read_config_file;
@@ -22,35 +32,117 @@ pub struct Args {
 
 #[derive(Parser, Debug)]
 pub enum SubCommand {
-    /// Fetch the provided try revision into a temporary directory.
+    /// Fetch the results of the provided try revision into its own temporary directory.
     Fetch {
         /// The try revision to fetch results from.
         rev: String,
     },
-    /// Delete the temporary directory.
-    Cleanup,
-    /// Process test results in the temporary directory and update test
+    /// List the try revisions whose results were fetched.
+    List,
+    /// Delete the results of a try revision, or all of them.
+    Cleanup {
+        /// The try revision whose results should be deleted.
+        #[arg(required_unless_present = "all")]
+        rev: Option<String>,
+        /// Delete the results of all try revisions.
+        #[arg(long, conflicts_with = "rev")]
+        all: bool,
+    },
+    /// Print the number of passing, failing, timing out and crashing tests per platform, and
+    /// check that no report is truncated or missing.
+    Summary {
+        /// The try revision whose results to use (defaults to the most recently fetched).
+        #[arg(long)]
+        run: Option<String>,
+    },
+    /// Process test results of a try revision and update test
     /// expectations in gecko.
     Expectations {
         /// Try revision to pull results from.
         ///
-        /// If not specified, read from the temporary directory
+        /// If not specified, use the results that were already fetched.
         #[arg(long)]
         fetch: Option<String>,
 
+        /// The try revision whose results to use (defaults to the most recently fetched).
+        #[arg(long, conflicts_with = "fetch")]
+        run: Option<String>,
+
         /// Remove the data after running the command
         #[arg(long)]
         cleanup: bool,
+
+        /// Update the expectations even if some reports are truncated or missing.
+        #[arg(long)]
+        force: bool,
+
+        /// How the results are combined with the current expectations.
+        #[arg(long, value_enum, default_value_t)]
+        preset: Preset,
+    },
+    /// Fetch the results of two try revisions and list the tests whose results changed.
+    Compare {
+        /// The try revision to compare against, typically before the update.
+        base: String,
+        /// The try revision with the changes, typically after the update.
+        new: String,
+        /// Optionally write the report into a file (defaults to stdout).
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Compare the results of the same tests across the rebuilds of a try push and list the
+    /// intermittent ones.
+    Flaky {
+        /// The try revision whose results to use (defaults to the most recently fetched).
+        #[arg(long)]
+        run: Option<String>,
+        /// Add the statuses the intermittent tests were seen with to their expectations in
+        /// gecko. The changes are left uncommitted.
+        #[arg(long)]
+        update: bool,
+    },
+    /// Update the WebGPU CTS vendored in gecko to another revision.
+    Update {
+        /// The CTS revision to update to.
+        #[arg(long)]
+        rev: String,
+
+        /// The bug number.
+        #[arg(short, long)]
+        bug: Option<String>,
+
+        /// Comma separated string of the 2 Phabricator revisions (to re-generate already
+        /// submitted patches).
+        #[arg(long)]
+        phab_revisions: Option<String>,
     },
     /// Run the CTS locally
     Run {
-        /// The test query
+        /// The test queries
         ///
-        /// For example "webgpu:api,validation,buffer,destroy:*"
-        query: String,
-    }
+        /// For example "webgpu:api,validation,buffer,destroy:*". Queries are resolved against
+        /// the CTS tests vendored in gecko, `*` matches any sequence of characters.
+        #[arg(required = true)]
+        queries: Vec<String>,
+
+        /// List the tests and subtests whose status differs from the current expectations.
+        #[arg(long)]
+        diff: bool,
+
+        /// How the results are combined with the current expectations, if they are updated.
+        #[arg(long, value_enum, default_value_t)]
+        preset: Preset,
+    },
 }
 
+/// The vendoring manifest of the WebGPU CTS in gecko.
+const CTS_MOZ_YAML: &str = "dom/webgpu/tests/cts/moz.yaml";
+const CTS_REPOSITORY: &str = "https://github.com/gpuweb/cts";
+
+// The order of the 2 gecko commits of `cts update`.
+const COMMIT_UPDATE: Option<usize> = Some(0);
+const COMMIT_VENDOR: Option<usize> = Some(1);
+
 fn temp_cts_result_dir(config: &Config) -> PathBuf {
     let mut path = config.gecko.path.parent().unwrap().to_owned();
     path.push("tmp-cts");
@@ -58,57 +150,165 @@ fn temp_cts_result_dir(config: &Config) -> PathBuf {
     path
 }
 
-fn fetch_cts_results_from_try(config: &Config, rev: &str) -> io::Result<()> {
-    let path = temp_cts_result_dir(config);
-    let gecko_dir_name = config.gecko.path.iter().last().unwrap().to_str().unwrap();
+fn fetch_cts_results_from_try(config: &Config, rev: &str, path: &Path) -> io::Result<()> {
+    let root_url = config
+        .gecko
+        .taskcluster_root_url
+        .as_deref()
+        .unwrap_or(taskcluster::DEFAULT_ROOT_URL);
 
-    println!(
-        " -- gecko dir: {:?}, parent {:?}",
-        config.gecko.path,
-        config.gecko.path.parent()
-    );
     println!(" -- creating temporary directory at {path:?}");
-    std::fs::create_dir_all(&path)?;
-    shell(
-        &path,
-        &format!("../{gecko_dir_name}/mach"),
-        &["wpt-fetch-logs", &format!("try:{rev}")],
-    )?;
+    std::fs::create_dir_all(path)?;
 
-    Ok(())
+    Taskcluster::new(root_url)?.fetch_reports(rev, path)
 }
 
-fn update_test_expectations(config: &Config) -> io::Result<()> {
-    let path = temp_cts_result_dir(config);
-    let mut json_files = Vec::new();
+/// Fetch the results of a try revision into its own directory and record what was fetched.
+fn fetch_run(config: &Config, rev: &str) -> io::Result<PathBuf> {
+    let root = temp_cts_result_dir(config);
+    let path = cts_runs::run_dir(&root, rev)?;
+    fetch_cts_results_from_try(config, rev, &path)?;
 
-    // This is ridiculously convoluted to do something that probably has
-    // a much simpler solution: resolve the path to each jsob file into an
-    // array of &str so that we cann invoke the command without the glob "*.json".
-    for file in std::fs::read_dir(&path)? {
-        let file = file.unwrap();
-        if !file.file_type().unwrap().is_file() {
-            continue;
-        }
-        let name = file.file_name().to_str().unwrap().to_string();
-        if name.ends_with(".json") {
-            let mut file_path = path.clone();
-            file_path.push(&name);
-            json_files.push(file_path.to_str().unwrap().to_string());
+    let manifest = RunManifest::new(&root, rev)?;
+    println!("Fetched {} reports into {path:?}", manifest.files.len());
+    manifest.save(&root)?;
+
+    Ok(path)
+}
+
+/// The directory of the results of `rev`, or of the most recently fetched revision.
+fn select_run(config: &Config, rev: Option<&str>) -> io::Result<PathBuf> {
+    let root = temp_cts_result_dir(config);
+    let rev = match rev {
+        Some(rev) => {
+            if RunManifest::load(&root, rev)?.is_none() {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!(
+                        "The results of {rev} were not fetched, run `moz-wgpu cts fetch {rev}`"
+                    ),
+                ));
+            }
+            rev.to_string()
         }
+        None => match cts_runs::list_runs(&root)?.pop() {
+            Some(run) => run.rev,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "No results were fetched, run `moz-wgpu cts fetch <rev>`",
+                ))
+            }
+        },
+    };
+
+    cts_runs::run_dir(&root, &rev)
+}
+
+fn list_command(config: &Config) -> io::Result<()> {
+    let root = temp_cts_result_dir(config);
+    let runs = cts_runs::list_runs(&root)?;
+    if runs.is_empty() {
+        println!("No results in {root:?}");
+    }
+    for run in runs {
+        println!(
+            " * {}: {} reports fetched on {}",
+            run.rev,
+            run.files.len(),
+            run.date
+        );
     }
 
-    let mut args: Vec<&str> = vec!["process-reports", "--preset=reset-contradictory"];
+    Ok(())
+}
+
+/// Load the reports of a directory and print a summary of their results.
+fn load_reports(path: &Path) -> io::Result<ReportSet> {
+    let reports = ReportSet::load(path)?;
+
+    println!("\n# CTS results in {path:?}\n");
+    print!("{}", wptreport::format_summary(&reports));
+    println!();
+
+    Ok(reports)
+}
+
+/// Update the expectations in gecko's metadata files and print what changed.
+fn update_test_expectations(
+    config: &Config,
+    reports: &ReportSet,
+    preset: Preset,
+) -> io::Result<cts_metadata::ExpectationUpdate> {
+    let results = cts_flaky::by_platform(&cts_flaky::analyze(reports));
+    let update = cts_metadata::update_expectations(&config.gecko.path, &results, preset)?;
+
+    println!("# Expectation changes\n");
+    print!("{}", cts_metadata::format_changes(&update));
+    println!();
+
+    Ok(update)
+}
+
+/// The results of a try revision for `cts compare`, fetched the first time they are needed.
+fn compare_results(config: &Config, rev: &str) -> io::Result<ReportSet> {
+    let root = temp_cts_result_dir(config);
+    let path = match RunManifest::load(&root, rev)? {
+        Some(run) => {
+            println!(" -- using the results fetched on {}", run.date);
+            cts_runs::run_dir(&root, rev)?
+        }
+        None => fetch_run(config, rev)?,
+    };
+
+    load_reports(&path)
+}
 
-    for file in &json_files {
-        args.push(&file);
+fn compare_command(
+    config: &Config,
+    base: &str,
+    new: &str,
+    output: &Option<PathBuf>,
+) -> io::Result<()> {
+    let base_reports = compare_results(config, base)?;
+    let new_reports = compare_results(config, new)?;
+
+    let comparison = cts_compare::compare(&base_reports, &new_reports);
+    let report = cts_compare::format_changes(&comparison, base, new);
+
+    match output {
+        Some(path) => {
+            println!("Writing the comparison to {path:?}");
+            std::fs::write(path, report)
+        }
+        None => {
+            print!("{report}");
+            Ok(())
+        }
     }
+}
 
-    if !shell(&config.gecko.path, "moz-webgpu-cts", &args)?.success() {
-        return Err(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            "Processing the cts test results failed",
-        ));
+fn flaky_command(config: &Config, run: Option<&str>, update: bool) -> io::Result<()> {
+    let reports = load_reports(&select_run(config, run)?)?;
+    let results = cts_flaky::analyze(&reports);
+
+    println!("# Test stability\n");
+    print!("{}", cts_flaky::format_report(&results));
+
+    if update {
+        // Only the tests that are intermittent on a configuration are written, the ones that
+        // reliably pass on a machine and reliably fail on another have a real failure.
+        let intermittent = cts_flaky::by_platform(
+            results
+                .iter()
+                .filter(|result| result.stability() == cts_flaky::Stability::Intermittent),
+        );
+
+        // The statuses that were seen are added to the ones that were already expected.
+        let update =
+            cts_metadata::update_expectations(&config.gecko.path, &intermittent, Preset::SameFx)?;
+        println!("\n# Expectation changes\n");
+        print!("{}", cts_metadata::format_changes(&update));
     }
 
     Ok(())
@@ -132,69 +332,276 @@ fn commit(config: &Config, commit_msg: &str) -> io::Result<()> {
     Ok(())
 }
 
-fn cleanup_command(config: &Config) -> io::Result<()> {
-    let path = temp_cts_result_dir(&config);
-    shell(
-        &current_dir().unwrap(),
-        "rm",
-        &["-rf", path.to_str().unwrap()],
+/// Update the expectations from the results of a try revision, fetching them first if `fetch`
+/// is set, and commit them.
+pub fn expectations_command(
+    config: &Config,
+    fetch: Option<&str>,
+    run: Option<&str>,
+    cleanup: bool,
+    force: bool,
+    preset: Preset,
+) -> io::Result<()> {
+    let path = match fetch {
+        Some(rev) => fetch_run(config, rev)?,
+        None => select_run(config, run)?,
+    };
+
+    let reports = load_reports(&path)?;
+    if !reports.problems.is_empty() && !force {
+        return Err(io::Error::other(
+            "Some test results are missing or incomplete, fetch them again or use --force",
+        ));
+    }
+
+    commit(
+        config,
+        "(Don't land) uncommitted changes before running the command",
     )?;
 
+    let update = update_test_expectations(config, &reports, preset)?;
+    if update.changes.is_empty() {
+        println!("Nothing to commit.");
+    } else {
+        add_files(config, &update.created_files)?;
+        commit(config, "Update WebGPU CTS test expectations")?;
+    }
+
+    if cleanup {
+        let rev = path.file_name().unwrap().to_str().unwrap();
+        cleanup_command(config, Some(rev))?;
+    }
+
     Ok(())
 }
 
-fn run_command(config: &Config, query: &str) -> io::Result<()> {
+/// Bump the CTS revision in its `moz.yaml`, vendor it and regenerate the wpt test listing, in
+/// two commits.
+fn update_command(
+    config: &Config,
+    rev: &str,
+    bug: Option<&str>,
+    phab_revisions: Option<&str>,
+) -> io::Result<()> {
     let mc = &config.gecko.path;
+    let phab_revisions: Option<Vec<String>> =
+        phab_revisions.map(|revs| revs.split(',').map(str::to_string).collect());
+    if phab_revisions.as_ref().is_some_and(|revs| revs.len() != 2) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Expected 2 comma separated Phabricator revisions",
+        ));
+    }
+    let message = |msg: &str, idx: Option<usize>| {
+        wgpu_update::commit_message(bug, phab_revisions.as_deref(), msg, idx)
+    };
+
+    commit(
+        config,
+        "(Don't land) Uncommitted changes before the CTS update.",
+    )?;
 
-    let mut test_cmd = "_mozilla/webgpu/cts/webgpu/".to_string();
+    let moz_yaml_path = mc.join(CTS_MOZ_YAML);
+    let tmp_moz_yaml_path = moz_yaml_path.with_file_name("tmp.moz.yaml");
+    println!("Parsing {moz_yaml_path:?}");
+    moz_yaml::update_moz_yaml(
+        io::BufReader::new(File::open(&moz_yaml_path)?),
+        BufWriter::new(File::create(&tmp_moz_yaml_path)?),
+        &[(CTS_REPOSITORY, rev)],
+    )?;
+    std::fs::rename(&tmp_moz_yaml_path, &moz_yaml_path)?;
+
+    commit(
+        config,
+        &message(
+            &format!("Update the WebGPU CTS to revision {rev}. r=#webgpu-reviewers"),
+            COMMIT_UPDATE,
+        ),
+    )?;
 
-    let query = query.strip_prefix("q=").unwrap_or(query);
-    let query = query.strip_prefix("webgpu:").unwrap_or(query);
-    let path = query.split(":").next().unwrap_or(query);
-    for item in path.split(",") {
-        test_cmd.push_str(item);
-        test_cmd.push_str("/");
+    if !shell(mc, "./mach", &["vendor", CTS_MOZ_YAML, "--ignore-modified"])?.success() {
+        return Err(io::Error::other("Vendoring the CTS failed"));
+    }
+    if !shell(mc, "./mach", &["wpt-manifest-update"])?.success() {
+        return Err(io::Error::other("Regenerating the wpt test listing failed"));
     }
-    test_cmd.push_str("cts.https.html?q=webgpu:");
-    test_cmd.push_str(query);
 
-    shell(
-        mc,
-        "./mach",
-        &["wpt", &test_cmd]
+    let vcs = config
+        .gecko
+        .vcs
+        .as_deref()
+        .map(Vcs::from_str)
+        .transpose()
+        .map_err(io::Error::other)?
+        .unwrap_or_default();
+    // Vendoring adds and removes files.
+    match vcs {
+        Vcs::Mercurial => shell(mc, "hg", &["addremove"])?,
+        Vcs::Git => shell(mc, "git", &["add", "-A"])?,
+    };
+
+    commit(
+        config,
+        &message(
+            "Vendor the WebGPU CTS changes. r=#webgpu-reviewers",
+            COMMIT_VENDOR,
+        ),
     )?;
 
+    println!("\nAll done! It would be a good idea to push to try and update the expectations.");
+
     Ok(())
 }
 
-pub fn command(args: &Args) -> io::Result<()> {
-    let config = read_config_file(&args.config)?;
+/// Start tracking new files so that they are part of the next commit.
+fn add_files(config: &Config, files: &[PathBuf]) -> io::Result<()> {
+    if files.is_empty() {
+        return Ok(());
+    }
 
-    match &args.sub_command {
-        SubCommand::Cleanup => cleanup_command(&config),
-        SubCommand::Fetch { rev } => fetch_cts_results_from_try(&config, &rev),
-        SubCommand::Expectations { fetch, cleanup } => {
-            if let Some(rev) = fetch {
-                fetch_cts_results_from_try(&config, &rev)?;
-            }
+    let vcs = config
+        .gecko
+        .vcs
+        .as_deref()
+        .map(Vcs::from_str)
+        .transpose()
+        .map_err(io::Error::other)?
+        .unwrap_or_default();
+    let mut args = vec!["add"];
+    args.extend(files.iter().map(|file| file.to_str().unwrap()));
 
-            commit(
-                &config,
-                "(Don't land) uncommitted changes before running the command",
-            )?;
+    match vcs {
+        Vcs::Mercurial => shell(&config.gecko.path, "hg", &args),
+        Vcs::Git => shell(&config.gecko.path, "git", &args),
+    }?;
 
-            update_test_expectations(&config)?;
+    Ok(())
+}
 
-            commit(&config, "Update WebGPU CTS test expectations")?;
+fn cleanup_command(config: &Config, rev: Option<&str>) -> io::Result<()> {
+    let root = temp_cts_result_dir(config);
+    let path = match rev {
+        Some(rev) => cts_runs::run_dir(&root, rev)?,
+        None => root.clone(),
+    };
+    if !path.starts_with(&root) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Refusing to delete {path:?}, which is not in {root:?}"),
+        ));
+    }
 
-            if *cleanup {
-                cleanup_command(&config)?;
-            }
+    match std::fs::remove_dir_all(&path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
 
-            Ok(())
+/// Where `cts run` writes the results of the local run.
+fn local_run_dir(config: &Config) -> PathBuf {
+    temp_cts_result_dir(config).join("local")
+}
+
+fn run_command(config: &Config, queries: &[String], diff: bool, preset: Preset) -> io::Result<()> {
+    let mc = &config.gecko.path;
+
+    let variants = cts_queries::list_variants(mc)?;
+    let (selected, unknown) = cts_queries::resolve(&variants, queries);
+    if !unknown.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "No CTS test matches {} in {:?}",
+                unknown.join(", "),
+                mc.join(cts_queries::CTS_TESTS_DIR)
+            ),
+        ));
+    }
+
+    println!("# Running {} CTS test variants\n", selected.len());
+    let mut file = None;
+    for variant in &selected {
+        if file != Some(&variant.file) {
+            file = Some(&variant.file);
+            println!("{}", variant.file.display());
         }
-        SubCommand::Run { query } => {
-            run_command(&config, &query)
+        println!(" * {}", variant.query);
+    }
+    println!();
+
+    let dir = local_run_dir(config);
+    std::fs::create_dir_all(&dir)?;
+    let report_path = dir.join("wptreport.json");
+    if report_path.exists() {
+        std::fs::remove_file(&report_path)?;
+    }
+    let log_arg = format!("--log-wptreport={}", report_path.to_str().unwrap());
+
+    let test_paths: Vec<String> = selected.iter().map(|variant| variant.test_path()).collect();
+    let mut args = vec!["wpt", log_arg.as_str()];
+    args.extend(test_paths.iter().map(String::as_str));
+
+    // mach fails when some results are unexpected, which is reported below.
+    shell(mc, "./mach", &args)?;
+
+    let reports = load_reports(&dir)?;
+    if reports.reports.is_empty() {
+        return Err(io::Error::other("The local run produced no results"));
+    }
+
+    if diff {
+        println!("# Differences with the current expectations\n");
+        print!("{}", wptreport::format_unexpected(&reports));
+        println!();
+    }
+
+    let answer = prompt("Update the expectations of the tests that ran? [y/N] ")?;
+    if answer.as_deref() == Some("y") {
+        let update = update_test_expectations(config, &reports, preset)?;
+        add_files(config, &update.created_files)?;
+        if !update.changes.is_empty() {
+            println!("The changes are not committed.");
+        }
+    }
+
+    Ok(())
+}
+
+pub fn command(args: &Args) -> io::Result<()> {
+    let config = read_config_file(&args.config)?;
+
+    match &args.sub_command {
+        SubCommand::Cleanup { rev, .. } => cleanup_command(&config, rev.as_deref()),
+        SubCommand::Fetch { rev } => fetch_run(&config, rev).map(|_| ()),
+        SubCommand::List => list_command(&config),
+        SubCommand::Summary { run } => {
+            load_reports(&select_run(&config, run.as_deref())?).map(|_| ())
         }
+        SubCommand::Compare { base, new, output } => compare_command(&config, base, new, output),
+        SubCommand::Flaky { run, update } => flaky_command(&config, run.as_deref(), *update),
+        SubCommand::Expectations {
+            fetch,
+            run,
+            cleanup,
+            force,
+            preset,
+        } => expectations_command(
+            &config,
+            fetch.as_deref(),
+            run.as_deref(),
+            *cleanup,
+            *force,
+            *preset,
+        ),
+        SubCommand::Update {
+            rev,
+            bug,
+            phab_revisions,
+        } => update_command(&config, rev, bug.as_deref(), phab_revisions.as_deref()),
+        SubCommand::Run {
+            queries,
+            diff,
+            preset,
+        } => run_command(&config, queries, *diff, *preset),
     }
 }
@@ -0,0 +1,253 @@
+use crate::wptreport::{Configuration, ReportSet, Status};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ChangeKind {
+    Crash,
+    Timeout,
+    Regression,
+    Fix,
+}
+
+impl ChangeKind {
+    pub fn title(self) -> &'static str {
+        match self {
+            ChangeKind::Crash => "Started crashing",
+            ChangeKind::Timeout => "Started timing out",
+            ChangeKind::Regression => "Regressions",
+            ChangeKind::Fix => "Newly passing",
+        }
+    }
+
+    fn classify(base: Status, new: Status) -> Option<Self> {
+        if base == new {
+            return None;
+        }
+
+        match new {
+            Status::Crash => Some(ChangeKind::Crash),
+            Status::Timeout => Some(ChangeKind::Timeout),
+            _ if new.is_success() && !base.is_success() => Some(ChangeKind::Fix),
+            _ if base.is_success() && !new.is_success() => Some(ChangeKind::Regression),
+            _ => None,
+        }
+    }
+}
+
+/// A test or subtest whose status changed between two sets of results.
+#[derive(Clone, Debug)]
+pub struct Change {
+    pub kind: ChangeKind,
+    pub configuration: Configuration,
+    pub query: String,
+    pub subtest: Option<String>,
+    pub base: Status,
+    pub new: Status,
+}
+
+pub struct Comparison {
+    pub changes: Vec<Change>,
+    /// The number of tests and subtests that were not compared because their status varied
+    /// across the retriggers of one of the try pushes.
+    pub intermittent: usize,
+}
+
+type Key = (Configuration, String, Option<String>);
+
+/// Every status of each test and subtest, keyed by configuration, test query and subtest name.
+/// Tests that were retriggered have more than one status.
+fn statuses(set: &ReportSet) -> BTreeMap<Key, BTreeSet<Status>> {
+    let mut statuses: BTreeMap<Key, BTreeSet<Status>> = BTreeMap::new();
+    for (configuration, result) in set.results_by_configuration() {
+        let query = result.query().to_string();
+        for subtest in &result.subtests {
+            statuses
+                .entry((
+                    configuration.clone(),
+                    query.clone(),
+                    Some(subtest.name.clone()),
+                ))
+                .or_default()
+                .insert(subtest.status);
+        }
+        statuses
+            .entry((configuration, query, None))
+            .or_default()
+            .insert(result.status);
+    }
+
+    statuses
+}
+
+/// The status of a test that had the same status in every run.
+fn stable_status(statuses: &BTreeSet<Status>) -> Option<Status> {
+    match statuses.len() {
+        1 => statuses.first().copied(),
+        _ => None,
+    }
+}
+
+/// The first two levels of a CTS query, for example `webgpu:api,validation` for
+/// `webgpu:api,validation,buffer,destroy:all_usages:*`.
+pub fn query_prefix(query: &str) -> String {
+    let mut parts = query.splitn(3, ':');
+    let suite = parts.next().unwrap_or_default();
+    let path: Vec<&str> = parts
+        .next()
+        .unwrap_or_default()
+        .split(',')
+        .take(2)
+        .collect();
+
+    format!("{suite}:{}", path.join(","))
+}
+
+/// The tests and subtests whose status changed from `base` to `new`. Tests that only ran in one
+/// of them are ignored, and so are the ones that were intermittent in either of them.
+pub fn compare(base: &ReportSet, new: &ReportSet) -> Comparison {
+    let base = statuses(base);
+    let mut comparison = Comparison {
+        changes: Vec::new(),
+        intermittent: 0,
+    };
+    for (key, new_statuses) in statuses(new) {
+        let Some(base_statuses) = base.get(&key) else {
+            continue;
+        };
+        if base_statuses == &new_statuses {
+            continue;
+        }
+
+        let (Some(base_status), Some(new_status)) =
+            (stable_status(base_statuses), stable_status(&new_statuses))
+        else {
+            comparison.intermittent += 1;
+            continue;
+        };
+
+        if let Some(kind) = ChangeKind::classify(base_status, new_status) {
+            let (configuration, query, subtest) = key;
+            comparison.changes.push(Change {
+                kind,
+                configuration,
+                query,
+                subtest,
+                base: base_status,
+                new: new_status,
+            });
+        }
+    }
+
+    comparison
+}
+
+/// A markdown report of the changes by kind, grouped by query prefix and platform.
+pub fn format_changes(comparison: &Comparison, base_rev: &str, new_rev: &str) -> String {
+    let mut text = String::new();
+    let _ = writeln!(text, "# CTS changes from {base_rev} to {new_rev}\n");
+    if comparison.intermittent > 0 {
+        let _ = writeln!(
+            text,
+            "{} tests and subtests were intermittent in one of the pushes and are not compared.\n",
+            comparison.intermittent
+        );
+    }
+    if comparison.changes.is_empty() {
+        let _ = writeln!(text, "No changes.");
+        return text;
+    }
+
+    let mut kinds: BTreeMap<ChangeKind, BTreeMap<(String, &Configuration), Vec<&Change>>> =
+        BTreeMap::new();
+    for change in &comparison.changes {
+        kinds
+            .entry(change.kind)
+            .or_default()
+            .entry((query_prefix(&change.query), &change.configuration))
+            .or_default()
+            .push(change);
+    }
+
+    for (kind, groups) in kinds {
+        let count: usize = groups.values().map(|group| group.len()).sum();
+        let _ = writeln!(text, "## {} ({count})\n", kind.title());
+        for ((prefix, configuration), group) in groups {
+            let _ = writeln!(text, "### {prefix} on {configuration}\n");
+            for change in group {
+                let subtest = change
+                    .subtest
+                    .as_ref()
+                    .map(|name| format!(" [{name}]"))
+                    .unwrap_or_default();
+                let _ = writeln!(
+                    text,
+                    " * {}{subtest}: {} -> {}",
+                    change.query, change.base, change.new
+                );
+            }
+            let _ = writeln!(text);
+        }
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wptreport::Report;
+    use std::path::PathBuf;
+
+    const TEST: &str = "/_mozilla/webgpu/cts/webgpu/api/validation/buffer/destroy/cts.https.html?q=webgpu:api,validation,buffer,destroy:*";
+
+    /// A report with a single test on a configuration.
+    fn report(processor: &str, status: &str) -> (PathBuf, Report) {
+        let json = format!(
+            r#"{{
+                "run_info": {{ "os": "mac", "debug": false, "os_version": "14.70", "processor": "{processor}" }},
+                "results": [{{ "test": "{TEST}", "status": "{status}" }}]
+            }}"#
+        );
+        (
+            PathBuf::from("report.json"),
+            serde_json::from_str(&json).unwrap(),
+        )
+    }
+
+    fn set(reports: Vec<(PathBuf, Report)>) -> ReportSet {
+        ReportSet {
+            reports,
+            problems: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn retriggers_and_configurations() {
+        let base = set(vec![
+            report("x86_64", "OK"),
+            report("x86_64", "OK"),
+            report("aarch64", "OK"),
+            report("aarch64", "OK"),
+        ]);
+        // Regressed on aarch64 in every run, intermittent on x86_64.
+        let new = set(vec![
+            report("x86_64", "OK"),
+            report("x86_64", "TIMEOUT"),
+            report("aarch64", "ERROR"),
+            report("aarch64", "ERROR"),
+        ]);
+
+        let comparison = compare(&base, &new);
+        assert_eq!(comparison.intermittent, 1);
+        assert_eq!(comparison.changes.len(), 1);
+
+        let change = &comparison.changes[0];
+        assert_eq!(change.kind, ChangeKind::Regression);
+        assert_eq!(change.configuration.processor, "aarch64");
+        assert_eq!(change.configuration.to_string(), "mac 14.70 aarch64/opt");
+        assert_eq!((change.base, change.new), (Status::Ok, Status::Error));
+    }
+}
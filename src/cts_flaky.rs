@@ -0,0 +1,230 @@
+use crate::wptreport::{self, BuildType, Configuration, Platform, ReportSet, Status};
+use std::{collections::BTreeMap, fmt::Write};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Stability {
+    StablePass,
+    StableFail,
+    Intermittent,
+}
+
+/// The statuses a test or subtest had on a configuration, across all the runs of the try push
+/// (typically the rebuilds requested with `moz-wgpu try --rebuild N`).
+#[derive(Clone, Debug)]
+pub struct TestStability {
+    /// The test's URL.
+    pub test: String,
+    pub subtest: Option<String>,
+    pub configuration: Configuration,
+    /// How many times each status was seen.
+    pub statuses: BTreeMap<Status, usize>,
+}
+
+impl TestStability {
+    pub fn stability(&self) -> Stability {
+        if self.statuses.len() > 1 {
+            Stability::Intermittent
+        } else if self.statuses.keys().all(|status| status.is_success()) {
+            Stability::StablePass
+        } else {
+            Stability::StableFail
+        }
+    }
+
+    pub fn platform(&self) -> &Platform {
+        &self.configuration.platform
+    }
+
+    pub fn runs(&self) -> usize {
+        self.statuses.values().sum()
+    }
+
+    /// The statuses, most frequent first.
+    pub fn statuses_by_frequency(&self) -> Vec<Status> {
+        let mut statuses: Vec<(Status, usize)> =
+            self.statuses.iter().map(|(s, c)| (*s, *c)).collect();
+        statuses.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+
+        statuses.into_iter().map(|(status, _)| status).collect()
+    }
+}
+
+type Groups = BTreeMap<(String, Option<String>, Configuration), BTreeMap<Status, usize>>;
+
+fn into_results(groups: Groups) -> Vec<TestStability> {
+    groups
+        .into_iter()
+        .map(|((test, subtest, configuration), statuses)| TestStability {
+            test,
+            subtest,
+            configuration,
+            statuses,
+        })
+        .collect()
+}
+
+/// Group the results of each test and subtest per configuration, so that a test that reliably
+/// passes on one machine and reliably fails on another is not seen as intermittent.
+pub fn analyze(set: &ReportSet) -> Vec<TestStability> {
+    let mut groups = Groups::new();
+    for (configuration, result) in set.results_by_configuration() {
+        for subtest in &result.subtests {
+            *groups
+                .entry((
+                    result.test.clone(),
+                    Some(subtest.name.clone()),
+                    configuration.clone(),
+                ))
+                .or_default()
+                .entry(subtest.status)
+                .or_default() += 1;
+        }
+        *groups
+            .entry((result.test.clone(), None, configuration))
+            .or_default()
+            .entry(result.status)
+            .or_default() += 1;
+    }
+
+    into_results(groups)
+}
+
+/// Combine the results of the configurations that share a platform, which the conditions of the
+/// metadata files cannot tell apart.
+pub fn by_platform<'a>(results: impl IntoIterator<Item = &'a TestStability>) -> Vec<TestStability> {
+    let mut groups = Groups::new();
+    for result in results {
+        let statuses = groups
+            .entry((
+                result.test.clone(),
+                result.subtest.clone(),
+                Configuration::from(result.platform().clone()),
+            ))
+            .or_default();
+        for (status, count) in &result.statuses {
+            *statuses.entry(*status).or_default() += count;
+        }
+    }
+
+    into_results(groups)
+}
+
+/// The number of tests and subtests in each category and the list of intermittent ones.
+pub fn format_report(results: &[TestStability]) -> String {
+    let mut text = String::new();
+    let count = |stability: Stability| {
+        results
+            .iter()
+            .filter(|result| result.stability() == stability)
+            .count()
+    };
+
+    let _ = writeln!(
+        text,
+        "Stable pass: {}, stable fail: {}, intermittent: {}\n",
+        count(Stability::StablePass),
+        count(Stability::StableFail),
+        count(Stability::Intermittent),
+    );
+
+    for result in results
+        .iter()
+        .filter(|result| result.stability() == Stability::Intermittent)
+    {
+        let (_, section) = wptreport::metadata_location(&result.test);
+        let subtest = result
+            .subtest
+            .as_ref()
+            .map(|name| format!(" [{name}]"))
+            .unwrap_or_default();
+        let statuses: Vec<String> = result
+            .statuses
+            .iter()
+            .map(|(status, count)| format!("{status} x{count}"))
+            .collect();
+        let _ = writeln!(
+            text,
+            " * {section}{subtest} on {}: {} ({} runs)",
+            result.configuration,
+            statuses.join(", "),
+            result.runs()
+        );
+    }
+
+    text
+}
+
+/// The wpt metadata condition matching a platform, for example `os == "win" and not debug`.
+pub fn platform_condition(platform: &Platform) -> String {
+    let debug = match platform.build_type {
+        BuildType::Debug => "debug",
+        BuildType::Opt => "not debug",
+    };
+
+    format!("os == {:?} and {debug}", platform.os)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wptreport::Report;
+    use std::path::PathBuf;
+
+    const TEST: &str = "/_mozilla/webgpu/cts/webgpu/api/validation/buffer/destroy/cts.https.html?q=webgpu:api,validation,buffer,destroy:*";
+
+    /// A report with a single test on a configuration.
+    fn report(processor: &str, status: &str) -> (PathBuf, Report) {
+        let json = format!(
+            r#"{{
+                "run_info": {{ "os": "mac", "debug": false, "os_version": "14.70", "processor": "{processor}" }},
+                "results": [{{ "test": "{TEST}", "status": "{status}" }}]
+            }}"#
+        );
+        (
+            PathBuf::from("report.json"),
+            serde_json::from_str(&json).unwrap(),
+        )
+    }
+
+    #[test]
+    fn stable_results_on_different_configurations() {
+        let set = ReportSet {
+            reports: vec![
+                report("aarch64", "OK"),
+                report("aarch64", "OK"),
+                report("x86_64", "ERROR"),
+                report("x86_64", "ERROR"),
+            ],
+            problems: Vec::new(),
+        };
+
+        let results = analyze(&set);
+        let stability: Vec<(&str, Stability)> = results
+            .iter()
+            .map(|result| (result.configuration.processor.as_str(), result.stability()))
+            .collect();
+        assert_eq!(
+            stability,
+            [
+                ("aarch64", Stability::StablePass),
+                ("x86_64", Stability::StableFail)
+            ]
+        );
+        assert!(format_report(&results).contains("intermittent: 0"));
+
+        // Combined per platform for the metadata, the two configurations share a condition.
+        let combined = by_platform(&results);
+        assert_eq!(combined.len(), 1);
+        assert_eq!(combined[0].configuration.to_string(), "mac/opt");
+        assert_eq!(combined[0].runs(), 4);
+        assert_eq!(combined[0].stability(), Stability::Intermittent);
+
+        // `cts flaky --update` only writes the tests that are intermittent on a configuration.
+        let intermittent = by_platform(
+            results
+                .iter()
+                .filter(|result| result.stability() == Stability::Intermittent),
+        );
+        assert!(intermittent.is_empty());
+    }
+}
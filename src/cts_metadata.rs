@@ -0,0 +1,987 @@
+use crate::{
+    cts_flaky::{platform_condition, TestStability},
+    wptreport::{self, Platform, Status},
+};
+use clap::ValueEnum;
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Write},
+    io,
+    path::{Path, PathBuf},
+};
+
+/// Where the expectations of the WebGPU CTS live in gecko.
+pub const CTS_METADATA_DIR: &str = "testing/web-platform/mozilla/meta/webgpu";
+
+/// How the observed statuses are combined with the current expectations.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Preset {
+    /// The results come from a new Firefox build: the observed statuses replace the
+    /// expectations of the platforms that ran.
+    NewFx,
+    /// The results come from the same Firefox build as the expectations: the observed statuses
+    /// are added to the expected ones.
+    SameFx,
+    /// Only the expectations that the observed statuses contradict are replaced.
+    #[default]
+    ResetContradictory,
+    /// The expectations of the tests that ran are rebuilt from the observed statuses, dropping
+    /// the ones of the platforms that did not run.
+    ResetAll,
+}
+
+impl Preset {
+    fn apply(self, current: &[Status], observed: &[Status]) -> Vec<Status> {
+        match self {
+            Preset::NewFx | Preset::ResetAll => observed.to_vec(),
+            Preset::SameFx => {
+                let mut statuses = current.to_vec();
+                for status in observed {
+                    if !statuses.contains(status) {
+                        statuses.push(*status);
+                    }
+                }
+                statuses
+            }
+            Preset::ResetContradictory => {
+                if observed.iter().all(|status| current.contains(status)) {
+                    current.to_vec()
+                } else {
+                    observed.to_vec()
+                }
+            }
+        }
+    }
+}
+
+/// A value of a property, used when `condition` matches (or always if there is no condition).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Branch {
+    pub condition: Option<String>,
+    pub value: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Property {
+    pub key: String,
+    pub branches: Vec<Branch>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Item {
+    /// An empty line, kept so that rewriting a file does not change its layout.
+    Blank,
+    Comment(String),
+    Property(Property),
+    Section(Section),
+}
+
+/// A section of a wpt metadata file (a test or a subtest). The root of a file is a section
+/// without name.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Section {
+    pub name: String,
+    pub items: Vec<Item>,
+}
+
+fn parse_error(line: usize, message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("line {}: {message}", line + 1),
+    )
+}
+
+/// The index of the first `:` that is not inside a string.
+fn find_separator(text: &str) -> Option<usize> {
+    let mut in_string = false;
+    for (idx, c) in text.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            ':' if !in_string => return Some(idx),
+            _ => {}
+        }
+    }
+
+    None
+}
+
+struct Line<'a> {
+    number: usize,
+    indent: usize,
+    text: &'a str,
+}
+
+fn parse_items(lines: &[Line], pos: &mut usize, indent: usize) -> io::Result<Vec<Item>> {
+    let mut items = Vec::new();
+    while let Some(line) = lines.get(*pos) {
+        if line.text.is_empty() {
+            // Empty lines belong to the level of the line that follows them, so that they stay
+            // between two tests when the last subtest of the first one is removed.
+            let next = lines[*pos..].iter().find(|next| !next.text.is_empty());
+            if next.map_or(0, |next| next.indent) < indent {
+                break;
+            }
+            *pos += 1;
+            items.push(Item::Blank);
+            continue;
+        }
+        if line.indent < indent {
+            break;
+        }
+        if line.indent > indent {
+            return Err(parse_error(line.number, "unexpected indentation"));
+        }
+        *pos += 1;
+
+        if line.text.starts_with('#') {
+            items.push(Item::Comment(line.text.to_string()));
+        } else if line.text.starts_with('[') && line.text.ends_with(']') {
+            let name = line.text[1..line.text.len() - 1].to_string();
+            let next = lines[*pos..].iter().find(|next| !next.text.is_empty());
+            let section_items = match next {
+                Some(next) if next.indent > indent => parse_items(lines, pos, next.indent)?,
+                _ => Vec::new(),
+            };
+            items.push(Item::Section(Section {
+                name,
+                items: section_items,
+            }));
+        } else {
+            let Some(idx) = line.text.find(':') else {
+                return Err(parse_error(line.number, "expected a property or a section"));
+            };
+            let key = line.text[..idx].trim().to_string();
+            let value = line.text[idx + 1..].trim();
+
+            let mut branches = Vec::new();
+            if !value.is_empty() {
+                branches.push(Branch {
+                    condition: None,
+                    value: value.to_string(),
+                });
+            }
+            while let Some(branch) = lines.get(*pos).filter(|branch| branch.indent > indent) {
+                *pos += 1;
+                let branch = match branch.text.strip_prefix("if ") {
+                    Some(conditional) => {
+                        let Some(idx) = find_separator(conditional) else {
+                            return Err(parse_error(
+                                branch.number,
+                                "expected `if <condition>: <value>`",
+                            ));
+                        };
+                        Branch {
+                            condition: Some(conditional[..idx].trim().to_string()),
+                            value: conditional[idx + 1..].trim().to_string(),
+                        }
+                    }
+                    None => Branch {
+                        condition: None,
+                        value: branch.text.to_string(),
+                    },
+                };
+                branches.push(branch);
+            }
+
+            items.push(Item::Property(Property { key, branches }));
+        }
+    }
+
+    Ok(items)
+}
+
+impl Section {
+    /// Parse a wpt metadata (`.ini`) file.
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let lines: Vec<Line> = text
+            .lines()
+            .enumerate()
+            .map(|(number, line)| Line {
+                number,
+                indent: line.len() - line.trim_start().len(),
+                text: line.trim(),
+            })
+            .collect();
+
+        let mut pos = 0;
+        let items = parse_items(&lines, &mut pos, 0)?;
+        if let Some(line) = lines.get(pos) {
+            return Err(parse_error(line.number, "unexpected indentation"));
+        }
+
+        Ok(Section {
+            name: String::new(),
+            items,
+        })
+    }
+
+    fn property_mut(&mut self, key: &str) -> Option<&mut Property> {
+        self.items.iter_mut().find_map(|item| match item {
+            Item::Property(property) if property.key == key => Some(property),
+            _ => None,
+        })
+    }
+
+    fn section(&self, name: &str) -> Option<&Section> {
+        self.items.iter().find_map(|item| match item {
+            Item::Section(section) if section.name == name => Some(section),
+            _ => None,
+        })
+    }
+
+    /// The subsection `name`, added at the end if it does not exist yet. `top_level` sections
+    /// are separated from the previous one by an empty line.
+    fn section_mut(&mut self, name: &str, top_level: bool) -> &mut Section {
+        let idx = self
+            .items
+            .iter()
+            .position(|item| matches!(item, Item::Section(section) if section.name == name));
+        let idx = idx.unwrap_or_else(|| {
+            if top_level && !matches!(self.items.last(), None | Some(Item::Blank)) {
+                self.items.push(Item::Blank);
+            }
+            self.items.push(Item::Section(Section {
+                name: name.to_string(),
+                items: Vec::new(),
+            }));
+            self.items.len() - 1
+        });
+
+        match &mut self.items[idx] {
+            Item::Section(section) => section,
+            _ => unreachable!(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.items.iter().all(|item| *item == Item::Blank)
+    }
+
+    /// Remove the subsection `name` if it is empty, along with the empty line that separates it
+    /// from the previous (or next) item.
+    fn remove_if_empty(&mut self, name: &str) {
+        let Some(idx) = self.items.iter().position(
+            |item| matches!(item, Item::Section(section) if section.name == name && section.is_empty()),
+        ) else {
+            return;
+        };
+
+        self.items.remove(idx);
+        if idx > 0 && self.items[idx - 1] == Item::Blank {
+            self.items.remove(idx - 1);
+        } else if self.items.get(idx) == Some(&Item::Blank) {
+            self.items.remove(idx);
+        }
+    }
+
+    fn write(&self, f: &mut fmt::Formatter, indent: usize) -> fmt::Result {
+        let pad = " ".repeat(indent);
+        for item in &self.items {
+            match item {
+                Item::Blank => writeln!(f)?,
+                Item::Comment(text) => writeln!(f, "{pad}{text}")?,
+                Item::Property(property) => match &property.branches[..] {
+                    [Branch {
+                        condition: None,
+                        value,
+                    }] => writeln!(f, "{pad}{}: {value}", property.key)?,
+                    branches => {
+                        writeln!(f, "{pad}{}:", property.key)?;
+                        for branch in branches {
+                            match &branch.condition {
+                                Some(condition) => {
+                                    writeln!(f, "{pad}  if {condition}: {}", branch.value)?
+                                }
+                                None => writeln!(f, "{pad}  {}", branch.value)?,
+                            }
+                        }
+                    }
+                },
+                Item::Section(section) => {
+                    writeln!(f, "{pad}[{}]", section.name)?;
+                    section.write(f, indent + 2)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Section {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write(f, 0)
+    }
+}
+
+/// Escape a test or subtest name to be used as a section name.
+pub fn escape_section_name(name: &str) -> String {
+    name.replace('\\', "\\\\").replace(']', "\\]")
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Eq,
+    NotEq,
+    LParen,
+    RParen,
+}
+
+fn tokenize(condition: &str) -> Option<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = condition.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' => {}
+            '(' => tokens.push(Token::LParen),
+            ')' => tokens.push(Token::RParen),
+            '=' if chars.next_if_eq(&'=').is_some() => tokens.push(Token::Eq),
+            '!' if chars.next_if_eq(&'=').is_some() => tokens.push(Token::NotEq),
+            '"' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next()? {
+                        '"' => break,
+                        c => text.push(c),
+                    }
+                }
+                tokens.push(Token::Str(text));
+            }
+            c if c.is_alphanumeric() || c == '_' || c == '.' => {
+                let mut ident = c.to_string();
+                while let Some(c) = chars.next_if(|c| c.is_alphanumeric() || *c == '_' || *c == '.')
+                {
+                    ident.push(c);
+                }
+                tokens.push(Token::Ident(ident));
+            }
+            _ => return None,
+        }
+    }
+
+    Some(tokens)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    Str(String),
+    Bool(bool),
+    Unknown,
+}
+
+/// Evaluates conditions such as `os == "win" and not debug` for a platform. Variables other
+/// than `os` and `debug` are unknown, which makes the conditions that depend on them unknown.
+struct ConditionEvaluator<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    platform: &'a Platform,
+}
+
+impl ConditionEvaluator<'_> {
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(self.tokens.get(self.pos), Some(Token::Ident(ident)) if ident == keyword)
+    }
+
+    fn or(&mut self) -> Option<Option<bool>> {
+        let mut result = self.and()?;
+        while self.peek_keyword("or") {
+            self.pos += 1;
+            let rhs = self.and()?;
+            result = match (result, rhs) {
+                (Some(true), _) | (_, Some(true)) => Some(true),
+                (Some(false), Some(false)) => Some(false),
+                _ => None,
+            };
+        }
+
+        Some(result)
+    }
+
+    fn and(&mut self) -> Option<Option<bool>> {
+        let mut result = self.not()?;
+        while self.peek_keyword("and") {
+            self.pos += 1;
+            let rhs = self.not()?;
+            result = match (result, rhs) {
+                (Some(false), _) | (_, Some(false)) => Some(false),
+                (Some(true), Some(true)) => Some(true),
+                _ => None,
+            };
+        }
+
+        Some(result)
+    }
+
+    fn not(&mut self) -> Option<Option<bool>> {
+        if self.peek_keyword("not") {
+            self.pos += 1;
+            return Some(self.not()?.map(|value| !value));
+        }
+
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Option<Option<bool>> {
+        if self.tokens.get(self.pos) == Some(&Token::LParen) {
+            self.pos += 1;
+            let result = self.or()?;
+            if self.tokens.get(self.pos) != Some(&Token::RParen) {
+                return None;
+            }
+            self.pos += 1;
+            return Some(result);
+        }
+
+        let lhs = self.value()?;
+        let negate = match self.tokens.get(self.pos) {
+            Some(Token::Eq) => false,
+            Some(Token::NotEq) => true,
+            _ => {
+                return Some(match lhs {
+                    Value::Bool(value) => Some(value),
+                    Value::Str(text) => Some(!text.is_empty()),
+                    Value::Unknown => None,
+                })
+            }
+        };
+        self.pos += 1;
+        let rhs = self.value()?;
+
+        Some(match (lhs, rhs) {
+            (Value::Unknown, _) | (_, Value::Unknown) => None,
+            (lhs, rhs) => Some((lhs == rhs) != negate),
+        })
+    }
+
+    fn value(&mut self) -> Option<Value> {
+        let token = self.tokens.get(self.pos)?.clone();
+        self.pos += 1;
+        Some(match token {
+            Token::Str(text) => Value::Str(text),
+            Token::Ident(ident) => match ident.as_str() {
+                "os" => Value::Str(self.platform.os.clone()),
+                "debug" => Value::Bool(self.platform.build_type == wptreport::BuildType::Debug),
+                "true" | "True" => Value::Bool(true),
+                "false" | "False" => Value::Bool(false),
+                _ => Value::Unknown,
+            },
+            _ => return None,
+        })
+    }
+}
+
+/// Whether `condition` is known to be true on `platform`.
+pub fn condition_matches(condition: &str, platform: &Platform) -> bool {
+    let Some(tokens) = tokenize(condition) else {
+        return false;
+    };
+    let token_count = tokens.len();
+    let mut evaluator = ConditionEvaluator {
+        tokens,
+        pos: 0,
+        platform,
+    };
+
+    match evaluator.or() {
+        Some(result) if evaluator.pos == token_count => result == Some(true),
+        _ => false,
+    }
+}
+
+fn parse_statuses(value: &str) -> Vec<Status> {
+    value
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .split(',')
+        .filter_map(|status| Status::parse(status.trim()))
+        .collect()
+}
+
+fn format_statuses(statuses: &[Status]) -> String {
+    match statuses {
+        [status] => status.to_string(),
+        statuses => {
+            let statuses: Vec<&str> = statuses.iter().map(|status| status.as_str()).collect();
+            format!("[{}]", statuses.join(", "))
+        }
+    }
+}
+
+/// The statuses that the `expected` branches allow on `platform`.
+fn evaluate(branches: &[Branch], platform: &Platform, default: Status) -> Vec<Status> {
+    let branch = branches.iter().find(|branch| match &branch.condition {
+        Some(condition) => condition_matches(condition, platform),
+        None => true,
+    });
+
+    match branch.map(|branch| parse_statuses(&branch.value)) {
+        Some(statuses) if !statuses.is_empty() => statuses,
+        _ => vec![default],
+    }
+}
+
+fn same_statuses(a: &[Status], b: &[Status]) -> bool {
+    let mut a = a.to_vec();
+    let mut b = b.to_vec();
+    a.sort();
+    a.dedup();
+    b.sort();
+    b.dedup();
+
+    a == b
+}
+
+/// A change of the statuses a test or subtest is expected to have on a platform.
+#[derive(Clone, Debug)]
+pub struct ExpectationChange {
+    pub file: PathBuf,
+    pub section: String,
+    pub subtest: Option<String>,
+    pub platform: Platform,
+    pub old: Vec<Status>,
+    pub new: Vec<Status>,
+}
+
+#[derive(Default)]
+pub struct ExpectationUpdate {
+    pub changes: Vec<ExpectationChange>,
+    /// Metadata files that did not exist before, relative to gecko's root.
+    pub created_files: Vec<PathBuf>,
+}
+
+/// Update the `expected` property of a test or subtest section with the results of each
+/// platform. Returns the platforms whose expectations changed, with the old and new statuses.
+fn update_expected(
+    section: &mut Section,
+    results: &[&TestStability],
+    preset: Preset,
+    default: Status,
+) -> Vec<(Platform, Vec<Status>, Vec<Status>)> {
+    let mut branches = section
+        .property_mut("expected")
+        .map(|property| std::mem::take(&mut property.branches))
+        .unwrap_or_default();
+    let old: Vec<Vec<Status>> = results
+        .iter()
+        .map(|result| evaluate(&branches, result.platform(), default))
+        .collect();
+
+    if preset == Preset::ResetAll {
+        branches.clear();
+    }
+
+    let mut changes = Vec::new();
+    for (result, old) in results.iter().zip(old) {
+        let new = preset.apply(&old, &result.statuses_by_frequency());
+        if preset != Preset::ResetAll && same_statuses(&old, &new) {
+            continue;
+        }
+
+        let condition = platform_condition(result.platform());
+        branches.retain(|branch| branch.condition.as_ref() != Some(&condition));
+        if !same_statuses(&evaluate(&branches, result.platform(), default), &new) {
+            branches.insert(
+                0,
+                Branch {
+                    condition: Some(condition),
+                    value: format_statuses(&new),
+                },
+            );
+        }
+
+        if !same_statuses(&old, &new) {
+            changes.push((result.platform().clone(), old, new));
+        }
+    }
+
+    // The property stays where it was. A new one goes after the other properties, before the
+    // subtest sections.
+    let position = section
+        .items
+        .iter()
+        .position(|item| matches!(item, Item::Property(property) if property.key == "expected"));
+    match (position, branches.is_empty()) {
+        (Some(idx), true) => {
+            section.items.remove(idx);
+        }
+        (Some(idx), false) => {
+            section.items[idx] = Item::Property(Property {
+                key: "expected".to_string(),
+                branches,
+            });
+        }
+        (None, true) => {}
+        (None, false) => {
+            let idx = section
+                .items
+                .iter()
+                .position(|item| matches!(item, Item::Section(_)))
+                .unwrap_or(section.items.len());
+            section.items.insert(
+                idx,
+                Item::Property(Property {
+                    key: "expected".to_string(),
+                    branches,
+                }),
+            );
+        }
+    }
+
+    changes
+}
+
+/// Update the CTS expectations in gecko's metadata files with the observed results.
+pub fn update_expectations(
+    gecko_path: &Path,
+    results: &[TestStability],
+    preset: Preset,
+) -> io::Result<ExpectationUpdate> {
+    // File -> (section, subtest) -> results per platform.
+    type Tests<'a> = BTreeMap<(String, Option<String>), Vec<&'a TestStability>>;
+    let mut files: BTreeMap<PathBuf, Tests> = BTreeMap::new();
+    for result in results {
+        let (file, section) = wptreport::metadata_location(&result.test);
+        if !file.starts_with(CTS_METADATA_DIR) {
+            continue;
+        }
+        files
+            .entry(file)
+            .or_default()
+            .entry((section, result.subtest.clone()))
+            .or_default()
+            .push(result);
+    }
+
+    let mut update = ExpectationUpdate::default();
+    for (file, tests) in files {
+        let path = gecko_path.join(&file);
+        let existed = path.exists();
+        let mut root = if existed {
+            Section::parse(&std::fs::read_to_string(&path)?)
+                .map_err(|e| io::Error::new(e.kind(), format!("{path:?}: {e}")))?
+        } else {
+            Section::default()
+        };
+
+        let mut changed = false;
+        for ((section_name, subtest), results) in tests {
+            // Sections that this update empties or creates without content are removed, the
+            // ones that were already empty are left alone.
+            let test_name = escape_section_name(&section_name);
+            let test_was_empty = root.section(&test_name).map(Section::is_empty);
+            let section = root.section_mut(&test_name, true);
+
+            let changes = match &subtest {
+                Some(name) => {
+                    let subtest_name = escape_section_name(name);
+                    let subtest_was_empty = section.section(&subtest_name).map(Section::is_empty);
+                    let subtest_section = section.section_mut(&subtest_name, false);
+                    let changes = update_expected(subtest_section, &results, preset, Status::Pass);
+                    if subtest_was_empty != Some(true) {
+                        section.remove_if_empty(&subtest_name);
+                    }
+                    changes
+                }
+                None => update_expected(section, &results, preset, Status::Ok),
+            };
+            if test_was_empty != Some(true) {
+                root.remove_if_empty(&test_name);
+            }
+
+            for (platform, old, new) in changes {
+                changed = true;
+                update.changes.push(ExpectationChange {
+                    file: file.clone(),
+                    section: section_name.clone(),
+                    subtest: subtest.clone(),
+                    platform,
+                    old,
+                    new,
+                });
+            }
+        }
+
+        if !changed {
+            continue;
+        }
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, root.to_string())?;
+        if !existed {
+            update.created_files.push(file);
+        }
+    }
+
+    Ok(update)
+}
+
+/// One line per changed expectation, grouped by file.
+pub fn format_changes(update: &ExpectationUpdate) -> String {
+    let mut text = String::new();
+    if update.changes.is_empty() {
+        let _ = writeln!(text, "No expectation changed.");
+        return text;
+    }
+
+    let _ = writeln!(
+        text,
+        "{} expectations changed in {} files ({} new files)\n",
+        update.changes.len(),
+        update
+            .changes
+            .iter()
+            .map(|change| &change.file)
+            .collect::<std::collections::BTreeSet<_>>()
+            .len(),
+        update.created_files.len()
+    );
+
+    let mut file = None;
+    for change in &update.changes {
+        if file != Some(&change.file) {
+            file = Some(&change.file);
+            let _ = writeln!(text, "{}", change.file.display());
+        }
+        let subtest = change
+            .subtest
+            .as_ref()
+            .map(|name| format!(" [{name}]"))
+            .unwrap_or_default();
+        let _ = writeln!(
+            text,
+            " * {}{subtest} on {}: {} -> {}",
+            change.section,
+            change.platform,
+            format_statuses(&change.old),
+            format_statuses(&change.new)
+        );
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wptreport::BuildType;
+
+    fn platform(os: &str, build_type: BuildType) -> Platform {
+        Platform {
+            os: os.to_string(),
+            build_type,
+        }
+    }
+
+    fn result(test: &str, subtest: Option<&str>, os: &str, statuses: &[Status]) -> TestStability {
+        TestStability {
+            test: test.to_string(),
+            subtest: subtest.map(str::to_string),
+            configuration: platform(os, BuildType::Opt).into(),
+            statuses: statuses.iter().map(|status| (*status, 1)).collect(),
+        }
+    }
+
+    #[test]
+    fn unmodified_round_trip() {
+        let text = r#"# Expectations of the buffer destruction tests.
+[cts.https.html?q=webgpu:api,validation,buffer,destroy:*]
+  implementation-status: backlog
+  expected:
+    if os == "win" and debug: [OK, TIMEOUT]
+    if os == "mac": CRASH
+  [all_usages:]
+    expected:
+      if os == "linux" and not debug: FAIL
+      [PASS, FAIL]
+
+  [twice:]
+    expected: FAIL
+  [empty\]:]
+
+
+[cts.https.html?q=webgpu:api,validation,buffer,mapping:*]
+  disabled: https://bugzilla.mozilla.org/show_bug.cgi?id=1234
+  [mapAsync,state,destroyed:]
+"#;
+        let root = Section::parse(text).unwrap();
+        assert_eq!(root.to_string(), text);
+    }
+
+    #[test]
+    fn condition_evaluation() {
+        let win_debug = platform("win", BuildType::Debug);
+        let linux_opt = platform("linux", BuildType::Opt);
+
+        assert!(condition_matches(r#"os == "win""#, &win_debug));
+        assert!(condition_matches(r#"os == "win" and debug"#, &win_debug));
+        assert!(!condition_matches(
+            r#"os == "win" and not debug"#,
+            &win_debug
+        ));
+        assert!(condition_matches(r#"os != "win""#, &linux_opt));
+        assert!(condition_matches(r#"os == "mac" or not debug"#, &linux_opt));
+        assert!(condition_matches(
+            r#"not (os == "mac" or os == "win")"#,
+            &linux_opt
+        ));
+        assert!(!condition_matches(
+            r#"(os == "linux" or os == "mac") and debug"#,
+            &linux_opt
+        ));
+
+        // Conditions that depend on other variables are only known to match when the known
+        // part decides them.
+        assert!(!condition_matches(r#"processor == "x86""#, &win_debug));
+        assert!(!condition_matches(r#"not processor == "x86""#, &win_debug));
+        assert!(!condition_matches(
+            r#"os == "win" and processor == "x86""#,
+            &win_debug
+        ));
+        assert!(condition_matches(
+            r#"os == "win" or processor == "x86""#,
+            &win_debug
+        ));
+        assert!(!condition_matches(
+            r#"os == "linux" and processor == "x86""#,
+            &win_debug
+        ));
+
+        // Conditions that cannot be parsed never match.
+        assert!(!condition_matches(r#"os =="#, &win_debug));
+        assert!(!condition_matches(r#"(os == "win""#, &win_debug));
+        assert!(!condition_matches(r#"os == "win" debug"#, &win_debug));
+    }
+
+    #[test]
+    fn presets_on_conflicting_expectations() {
+        let text = r#"expected:
+  if os == "win": FAIL
+  if os == "mac": [FAIL, PASS]
+"#;
+        // Windows contradicts its expectation, macOS only shows one of the expected statuses,
+        // Linux matches the default.
+        let results = [
+            result("/test", Some("a"), "win", &[Status::Pass]),
+            result("/test", Some("a"), "mac", &[Status::Fail]),
+            result("/test", Some("a"), "linux", &[Status::Pass]),
+        ];
+        let results: Vec<&TestStability> = results.iter().collect();
+
+        let update = |preset| {
+            let mut section = Section::parse(text).unwrap();
+            let changes = update_expected(&mut section, &results, preset, Status::Pass);
+            let changes: Vec<(String, Vec<Status>, Vec<Status>)> = changes
+                .into_iter()
+                .map(|(platform, old, new)| (platform.os, old, new))
+                .collect();
+            (section.to_string(), changes)
+        };
+
+        let (text, changes) = update(Preset::NewFx);
+        assert_eq!(
+            text,
+            r#"expected:
+  if os == "mac" and not debug: FAIL
+  if os == "win" and not debug: PASS
+  if os == "win": FAIL
+  if os == "mac": [FAIL, PASS]
+"#
+        );
+        assert_eq!(
+            changes,
+            [
+                ("win".to_string(), vec![Status::Fail], vec![Status::Pass]),
+                (
+                    "mac".to_string(),
+                    vec![Status::Fail, Status::Pass],
+                    vec![Status::Fail]
+                ),
+            ]
+        );
+
+        let (text, changes) = update(Preset::SameFx);
+        assert_eq!(
+            text,
+            r#"expected:
+  if os == "win" and not debug: [FAIL, PASS]
+  if os == "win": FAIL
+  if os == "mac": [FAIL, PASS]
+"#
+        );
+        assert_eq!(
+            changes,
+            [(
+                "win".to_string(),
+                vec![Status::Fail],
+                vec![Status::Fail, Status::Pass]
+            )]
+        );
+
+        let (text, changes) = update(Preset::ResetContradictory);
+        assert_eq!(
+            text,
+            r#"expected:
+  if os == "win" and not debug: PASS
+  if os == "win": FAIL
+  if os == "mac": [FAIL, PASS]
+"#
+        );
+        assert_eq!(
+            changes,
+            [("win".to_string(), vec![Status::Fail], vec![Status::Pass])]
+        );
+
+        // The expectations of the platforms that did not run (Windows debug) are dropped.
+        let (text, changes) = update(Preset::ResetAll);
+        assert_eq!(
+            text,
+            r#"expected:
+  if os == "mac" and not debug: FAIL
+"#
+        );
+        assert_eq!(changes.len(), 2);
+    }
+
+    #[test]
+    fn update_only_prunes_the_sections_it_empties() {
+        let gecko = std::env::temp_dir().join(format!("moz-wgpu-{}-prune", std::process::id()));
+        let _ = std::fs::remove_dir_all(&gecko);
+        let file = Path::new(CTS_METADATA_DIR)
+            .join("cts/webgpu/api/validation/buffer/destroy/cts.https.html.ini");
+        std::fs::create_dir_all(gecko.join(&file).parent().unwrap()).unwrap();
+        std::fs::write(
+            gecko.join(&file),
+            r#"[cts.https.html?q=webgpu:api,validation,buffer,destroy:*]
+  [already_empty:]
+  [fixed:]
+    expected:
+      if os == "linux" and not debug: FAIL
+
+[cts.https.html?q=webgpu:api,validation,buffer,destroy:twice:*]
+"#,
+        )
+        .unwrap();
+
+        let test = "/_mozilla/webgpu/cts/webgpu/api/validation/buffer/destroy/cts.https.html?q=webgpu:api,validation,buffer,destroy:*";
+        let results = [
+            result(test, Some("fixed:"), "linux", &[Status::Pass]),
+            result(test, Some("new:"), "linux", &[Status::Pass]),
+        ];
+        let update = update_expectations(&gecko, &results, Preset::ResetContradictory).unwrap();
+
+        assert_eq!(update.changes.len(), 1);
+        assert!(update.created_files.is_empty());
+        assert_eq!(
+            std::fs::read_to_string(gecko.join(&file)).unwrap(),
+            r#"[cts.https.html?q=webgpu:api,validation,buffer,destroy:*]
+  [already_empty:]
+
+[cts.https.html?q=webgpu:api,validation,buffer,destroy:twice:*]
+"#
+        );
+    }
+}
@@ -0,0 +1,239 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+/// Where the vendored WebGPU CTS tests live in gecko, served as `/_mozilla/webgpu/`.
+pub const CTS_TESTS_DIR: &str = "testing/web-platform/mozilla/tests/webgpu";
+
+/// A variant of a CTS test file, for example
+/// `cts/webgpu/api/validation/buffer/destroy/cts.https.html` with the query
+/// `webgpu:api,validation,buffer,destroy:*`.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Variant {
+    /// The test file, relative to `CTS_TESTS_DIR`.
+    pub file: PathBuf,
+    pub query: String,
+}
+
+impl Variant {
+    /// The test path to pass to `./mach wpt`.
+    pub fn test_path(&self) -> String {
+        format!("_mozilla/webgpu/{}?q={}", self.file.display(), self.query)
+    }
+}
+
+/// The queries of the `<meta name=variant content='?q=...'>` lines of a test file.
+fn variant_queries(html: &str) -> Vec<String> {
+    html.lines()
+        .filter(|line| line.contains("name=variant"))
+        .filter_map(|line| {
+            let (_, query) = line.split_once("?q=")?;
+            let end = query.find(['\'', '"'])?;
+            Some(query[..end].to_string())
+        })
+        .collect()
+}
+
+fn collect_variants(root: &Path, dir: &Path, variants: &mut Vec<Variant>) -> io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            collect_variants(root, &path, variants)?;
+        } else if path.extension().and_then(|ext| ext.to_str()) == Some("html") {
+            let file = path.strip_prefix(root).unwrap().to_path_buf();
+            for query in variant_queries(&std::fs::read_to_string(&path)?) {
+                variants.push(Variant {
+                    file: file.clone(),
+                    query,
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// All the test variants of the CTS vendored in gecko, sorted by file and query.
+pub fn list_variants(gecko_path: &Path) -> io::Result<Vec<Variant>> {
+    let root = gecko_path.join(CTS_TESTS_DIR);
+    if !root.is_dir() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("The WebGPU CTS is not vendored in {root:?}"),
+        ));
+    }
+
+    let mut variants = Vec::new();
+    collect_variants(&root, &root, &mut variants)?;
+    variants.sort();
+
+    Ok(variants)
+}
+
+/// Accept `q=webgpu:...`, `webgpu:...` and queries without the suite name.
+pub fn normalize_query(query: &str) -> String {
+    let query = query.trim();
+    let query = query.strip_prefix("?").unwrap_or(query);
+    let query = query.strip_prefix("q=").unwrap_or(query);
+    if query.starts_with("webgpu:") {
+        query.to_string()
+    } else {
+        format!("webgpu:{query}")
+    }
+}
+
+/// Whether `text` matches `pattern`, in which `*` matches any sequence of characters.
+fn glob_matches(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+
+    let mut parts: Vec<&str> = parts.collect();
+    let Some(last) = parts.pop() else {
+        // No wildcard.
+        return rest.is_empty();
+    };
+    for part in parts {
+        match rest.find(part) {
+            Some(idx) => rest = &rest[idx + part.len()..],
+            None => return false,
+        }
+    }
+
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+/// The variants a query selects. A query that is more specific than a variant (for example a
+/// single test of a file whose variant is `...:*`) selects the variant containing it, since
+/// wpt can only run the variants listed in the test files.
+fn query_matches(query: &str, variant: &str) -> bool {
+    if glob_matches(query, variant) {
+        return true;
+    }
+
+    match variant.strip_suffix('*') {
+        Some(prefix) => query.starts_with(prefix),
+        None => false,
+    }
+}
+
+/// The variants selected by the queries (without duplicates) and the queries that select
+/// nothing.
+pub fn resolve<'a>(variants: &'a [Variant], queries: &[String]) -> (Vec<&'a Variant>, Vec<String>) {
+    let mut selected: Vec<&Variant> = Vec::new();
+    let mut unknown = Vec::new();
+    for query in queries {
+        let query = normalize_query(query);
+        let mut found = false;
+        for variant in variants
+            .iter()
+            .filter(|variant| query_matches(&query, &variant.query))
+        {
+            found = true;
+            if !selected.contains(&variant) {
+                selected.push(variant);
+            }
+        }
+        if !found {
+            unknown.push(query);
+        }
+    }
+    selected.sort();
+
+    (selected, unknown)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variant(file: &str, query: &str) -> Variant {
+        Variant {
+            file: PathBuf::from(file),
+            query: query.to_string(),
+        }
+    }
+
+    #[test]
+    fn wildcards() {
+        let query = "webgpu:api,validation,buffer,destroy:*";
+        assert!(glob_matches(query, query));
+        assert!(glob_matches("*", query));
+        assert!(glob_matches("*destroy:*", query));
+        assert!(glob_matches("webgpu:api,*,buffer,*", query));
+        assert!(glob_matches("webgpu:api,validation,*", query));
+        assert!(glob_matches("webgpu:*:*", query));
+        assert!(!glob_matches("webgpu:api,operation,*", query));
+        assert!(!glob_matches("*,texture,*", query));
+        assert!(!glob_matches("webgpu:api,validation", query));
+        // The end of the pattern cannot overlap with what the middle matched.
+        assert!(!glob_matches("webgpu:*destroy:*destroy:*", query));
+    }
+
+    #[test]
+    fn normalization() {
+        let query = "webgpu:api,validation,buffer,destroy:*";
+        assert_eq!(normalize_query(query), query);
+        assert_eq!(normalize_query(&format!(" ?q={query}\n")), query);
+        assert_eq!(normalize_query("q=api,validation,buffer,destroy:*"), query);
+
+        // The trailing wildcards of test and parameter queries are kept.
+        let subcase = r#"webgpu:api,validation,buffer,destroy:all_usages:usage=4;*"#;
+        assert_eq!(normalize_query(&format!("?q={subcase}")), subcase);
+        assert_eq!(
+            normalize_query("api,validation,buffer,destroy:all_usages:*"),
+            "webgpu:api,validation,buffer,destroy:all_usages:*"
+        );
+    }
+
+    #[test]
+    fn resolution() {
+        let destroy = variant(
+            "cts/webgpu/api/validation/buffer/destroy/cts.https.html",
+            "webgpu:api,validation,buffer,destroy:*",
+        );
+        let mapping = variant(
+            "cts/webgpu/api/validation/buffer/mapping/cts.https.html",
+            "webgpu:api,validation,buffer,mapping:mapAsync,state,destroyed:*",
+        );
+        let copy = variant(
+            "cts/webgpu/api/operation/command_buffer/copy/cts.https.html",
+            "webgpu:api,operation,command_buffer,copy:*",
+        );
+        let variants = [destroy.clone(), mapping.clone(), copy.clone()];
+        let queries = |queries: &[&str]| -> Vec<String> {
+            queries.iter().map(|query| query.to_string()).collect()
+        };
+
+        // Overlapping queries select each variant once.
+        let (selected, unknown) = resolve(
+            &variants,
+            &queries(&["api,validation,buffer,*", "webgpu:api,validation,*"]),
+        );
+        assert_eq!(selected, [&destroy, &mapping]);
+        assert!(unknown.is_empty());
+
+        // A test or a subcase selects the variant of its file.
+        let (selected, unknown) = resolve(
+            &variants,
+            &queries(&[
+                "webgpu:api,validation,buffer,destroy:all_usages:*",
+                "webgpu:api,operation,command_buffer,copy:b2b:size=4;*",
+            ]),
+        );
+        assert_eq!(selected, [&copy, &destroy]);
+        assert!(unknown.is_empty());
+
+        // Queries that match nothing are reported, the others still select their variants.
+        let (selected, unknown) = resolve(
+            &variants,
+            &queries(&["webgpu:api,validation,texture,*", "api,operation,*"]),
+        );
+        assert_eq!(selected, [&copy]);
+        assert_eq!(unknown, ["webgpu:api,validation,texture,*"]);
+    }
+}
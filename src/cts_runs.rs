@@ -0,0 +1,144 @@
+use serde_derive::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+const MANIFEST_FILE_NAME: &str = "manifest.toml";
+
+/// What was fetched for a try revision, stored as `manifest.toml` in the run's directory.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct RunManifest {
+    /// The try revision.
+    pub rev: String,
+    /// When the results were fetched, in RFC 3339 format.
+    pub date: String,
+    /// The wptreport files of the run, relative to its directory.
+    pub files: Vec<String>,
+}
+
+/// Whether `rev` is a plain revision name, which cannot point outside of the directory of the
+/// runs once joined to it.
+fn is_valid_rev(rev: &str) -> bool {
+    !rev.is_empty() && rev.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+/// The directory containing the results of a try revision.
+pub fn run_dir(root: &Path, rev: &str) -> io::Result<PathBuf> {
+    if !is_valid_rev(rev) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Invalid revision {rev:?}, expected a revision hash"),
+        ));
+    }
+
+    Ok(root.join(rev))
+}
+
+impl RunManifest {
+    /// Describe the wptreport files currently in the directory of the run.
+    pub fn new(root: &Path, rev: &str) -> io::Result<Self> {
+        let mut files = Vec::new();
+        for entry in std::fs::read_dir(run_dir(root, rev)?)? {
+            let name = entry?.file_name().to_string_lossy().to_string();
+            if name.ends_with(".json") {
+                files.push(name);
+            }
+        }
+        files.sort();
+
+        Ok(RunManifest {
+            rev: rev.to_string(),
+            date: chrono::Utc::now().to_rfc3339(),
+            files,
+        })
+    }
+
+    /// Load the manifest of a run, `None` if the run was never (completely) fetched.
+    pub fn load(root: &Path, rev: &str) -> io::Result<Option<Self>> {
+        let path = run_dir(root, rev)?.join(MANIFEST_FILE_NAME);
+        let mut file = match File::open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        let mut buf = String::new();
+        file.read_to_string(&mut buf)?;
+
+        toml::from_str(&buf).map(Some).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Failed to parse {path:?}: {e}"),
+            )
+        })
+    }
+
+    pub fn save(&self, root: &Path) -> io::Result<()> {
+        let text = toml::to_string(self).map_err(io::Error::other)?;
+        let path = run_dir(root, &self.rev)?.join(MANIFEST_FILE_NAME);
+        let mut file = io::BufWriter::new(File::create(path)?);
+        file.write_all(text.as_bytes())?;
+        file.flush()
+    }
+}
+
+/// The runs that were fetched into `root`, oldest first.
+pub fn list_runs(root: &Path) -> io::Result<Vec<RunManifest>> {
+    let entries = match std::fs::read_dir(root) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut runs = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        let rev = entry.file_name().to_string_lossy().to_string();
+        if !is_valid_rev(&rev) {
+            continue;
+        }
+        if let Some(manifest) = RunManifest::load(root, &rev)? {
+            runs.push(manifest);
+        }
+    }
+    runs.sort_by(|a, b| a.date.cmp(&b.date));
+
+    Ok(runs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_dir_stays_in_root() {
+        let root = Path::new("/work/tmp-cts");
+        assert_eq!(
+            run_dir(root, "4a4ba7a63a6d").unwrap(),
+            root.join("4a4ba7a63a6d")
+        );
+        for rev in [
+            "",
+            "/",
+            "..",
+            ".",
+            "../mozilla-central",
+            "a/b",
+            "a\\b",
+            "/etc",
+            "4a4b.json",
+        ] {
+            assert_eq!(
+                run_dir(root, rev).unwrap_err().kind(),
+                io::ErrorKind::InvalidInput,
+                "{rev:?}"
+            );
+        }
+    }
+}
@@ -1,7 +1,18 @@
 use clap::Parser;
-use std::{io, path::PathBuf, str::FromStr};
-
-use crate::{read_config_file, shell, Vcs};
+use std::{
+    io::{self, BufRead, Write},
+    path::PathBuf,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    cts,
+    cts_metadata::Preset,
+    read_config_file, shell,
+    treeherder::{self, Treeherder},
+    Vcs,
+};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -11,6 +22,11 @@ pub struct BugzillaArgs {
     /// Open the bugzilla url in firefox.
     #[arg(short, long)]
     open: bool,
+
+    /// Pre-fill the bug's description with the content of a file (for example the changelog
+    /// written by `moz-wgpu audit --changelog`).
+    #[arg(long, value_name = "FILE")]
+    description: Option<PathBuf>,
 }
 
 #[derive(Parser, Debug)]
@@ -19,6 +35,32 @@ pub struct MachArgs {
     command: Vec<String>,
 }
 
+#[derive(Parser, Debug)]
+pub enum TrySubCommand {
+    /// Show how many of the WebGPU jobs of a try push are running, failed and passed.
+    Status {
+        /// The try revision.
+        rev: String,
+
+        /// Wait until all the WebGPU jobs have finished, then update the CTS expectations with
+        /// their results.
+        #[arg(long)]
+        wait: bool,
+
+        /// How often to check the status when waiting, in seconds.
+        #[arg(long, default_value_t = 300, value_parser = clap::value_parser!(u64).range(10..=3600))]
+        interval: u64,
+
+        /// Give up waiting after this many minutes.
+        #[arg(long, default_value_t = 720)]
+        timeout: u64,
+
+        /// How the results are combined with the current expectations after waiting.
+        #[arg(long, value_enum, default_value_t)]
+        preset: Preset,
+    },
+}
+
 pub fn file_bug(args: &BugzillaArgs) -> io::Result<()> {
     let mut url = "https://bugzilla.mozilla.org/enter_bug.cgi?".to_string();
     url.push_str("&assigned_to=nobody%40mozilla.org");
@@ -47,6 +89,12 @@ pub fn file_bug(args: &BugzillaArgs) -> io::Result<()> {
         url.push_str(&msg);
     }
 
+    if let Some(path) = &args.description {
+        let description = std::fs::read_to_string(path)?;
+        url.push_str("&comment=");
+        url.push_str(&percent_encode(&description));
+    }
+
     println!("{url}");
 
     if args.open {
@@ -56,6 +104,34 @@ pub fn file_bug(args: &BugzillaArgs) -> io::Result<()> {
     Ok(())
 }
 
+/// Escape everything except unreserved characters in a url query parameter.
+fn percent_encode(src: &str) -> String {
+    let mut result = String::new();
+    for byte in src.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                result.push(byte as char)
+            }
+            _ => result.push_str(&format!("%{byte:02X}")),
+        }
+    }
+
+    result
+}
+
+/// Print `question` and read the answer from stdin, `None` at the end of the input.
+pub fn prompt(question: &str) -> io::Result<Option<String>> {
+    print!("{question}");
+    io::stdout().flush()?;
+
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer)? == 0 {
+        return Ok(None);
+    }
+
+    Ok(Some(answer.trim().to_string()))
+}
+
 pub fn hg_histedit() -> io::Result<()> {
     let config = read_config_file(&None)?;
 
@@ -101,3 +177,71 @@ pub fn push_to_try(rebuild: Option<u8>) -> io::Result<()> {
 
     Ok(())
 }
+
+pub fn try_command(command: &TrySubCommand) -> io::Result<()> {
+    let TrySubCommand::Status {
+        rev,
+        wait,
+        interval,
+        timeout,
+        preset,
+    } = command;
+    let config = read_config_file(&None)?;
+    let url = config
+        .gecko
+        .treeherder_url
+        .as_deref()
+        .unwrap_or(treeherder::DEFAULT_URL);
+    let treeherder = Treeherder::new(url)?;
+
+    // While waiting, a push whose jobs are not scheduled yet and the errors that may go away
+    // are treated as jobs that have not finished.
+    let deadline = Instant::now() + Duration::from_secs(timeout * 60);
+    loop {
+        match treeherder.jobs(rev) {
+            Ok(jobs) => {
+                let summary = treeherder::summarize(&jobs);
+                if summary.is_empty() {
+                    if !wait {
+                        return Err(io::Error::other(format!(
+                            "No WebGPU job in the try push of {rev}"
+                        )));
+                    }
+                    println!("\nNo WebGPU job in the try push of {rev} yet.");
+                } else {
+                    println!("\n# WebGPU jobs of try:{rev}\n");
+                    print!("{}", treeherder::format_summary(&summary));
+
+                    if treeherder::is_complete(&summary) {
+                        println!("\nAll the WebGPU jobs have finished.");
+                        break;
+                    }
+                    if !wait {
+                        return Ok(());
+                    }
+                }
+            }
+            Err(e) if *wait && treeherder::is_transient(&e) => {
+                println!("\nCould not get the jobs of try:{rev}: {e}");
+            }
+            Err(e) => return Err(e),
+        }
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("The WebGPU jobs of try:{rev} did not finish within {timeout} minutes"),
+            ));
+        }
+        let delay = Duration::from_secs(*interval).min(remaining);
+        println!("\nChecking again in {} seconds...", delay.as_secs());
+        std::thread::sleep(delay);
+    }
+
+    if *wait {
+        cts::expectations_command(&config, Some(rev), None, false, false, *preset)?;
+    }
+
+    Ok(())
+}
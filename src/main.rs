@@ -1,10 +1,40 @@
+/// Like `println!`, but for progress messages that go to stderr when the command writes a
+/// machine-readable report to stdout (see `send_progress_to_stderr`).
+macro_rules! progress {
+    ($($arg:tt)*) => {
+        if $crate::progress_on_stderr() {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
+
 mod audit;
+mod audit_dependencies;
+mod audit_history;
+mod audit_identity;
+mod audit_ledger;
+mod audit_policy;
+mod audit_report;
+mod audit_review;
+mod audit_risk;
+mod audit_unsafe;
 mod cargo_lock;
 mod cargo_toml;
+mod changelog;
 mod cts;
+mod cts_compare;
+mod cts_flaky;
+mod cts_metadata;
+mod cts_queries;
+mod cts_runs;
 mod helpers;
 mod moz_yaml;
+mod taskcluster;
+mod treeherder;
 mod wgpu_update;
+mod wptreport;
 
 use anyhow::bail;
 use clap::Parser;
@@ -18,9 +48,22 @@ use std::{
     path::{Path, PathBuf},
     process::{Command, ExitStatus, Stdio},
     str::FromStr,
+    sync::atomic::{AtomicBool, Ordering},
 };
 
-const DEFAULT_WGPU_REPOSITORY: &'static str = "https://github.com/gfx-rs/wgpu";
+static PROGRESS_ON_STDERR: AtomicBool = AtomicBool::new(false);
+
+/// Print progress messages and the output of the commands run by `shell` to stderr, so that
+/// a report written to stdout can be piped into another tool.
+pub fn send_progress_to_stderr() {
+    PROGRESS_ON_STDERR.store(true, Ordering::Relaxed);
+}
+
+pub fn progress_on_stderr() -> bool {
+    PROGRESS_ON_STDERR.load(Ordering::Relaxed)
+}
+
+const DEFAULT_WGPU_REPOSITORY: &str = "https://github.com/gfx-rs/wgpu";
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -36,10 +79,14 @@ pub enum Args {
     /// Run `hg histedit` in the `gecko` directory.
     Histedit,
     /// Push a try run to Firefox's CI.
+    #[command(args_conflicts_with_subcommands = true)]
     Try {
         /// Request that all jobs be re-run <REBUILD> times.
         #[arg(long)]
         rebuild: Option<u8>,
+
+        #[command(subcommand)]
+        command: Option<helpers::TrySubCommand>,
     },
     /// Update this tool to its latest version using cargo.
     SelfUpdate,
@@ -56,9 +103,16 @@ struct Config {
 }
 
 #[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "kebab-case")]
 pub struct Gecko {
     path: PathBuf,
     vcs: Option<String>,
+    // Where `moz-wgpu cts` fetches try results from. Defaults to the Firefox CI deployment of
+    // Taskcluster, can be pointed at a local stand-in for testing purposes.
+    taskcluster_root_url: Option<String>,
+    // Where `moz-wgpu try status` looks up the jobs of try pushes. Defaults to
+    // https://treeherder.mozilla.org, can be pointed at a local stand-in for testing purposes.
+    treeherder_url: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -70,7 +124,15 @@ pub struct GithubProject {
     #[serde(default = "default_remote")]
     upstream_remote: String,
     trusted_reviewers: Vec<String>,
+    #[serde(default)]
+    trust_policy: audit_policy::TrustPolicy,
     latest_commit: Option<PathBuf>,
+    // Where `moz-wgpu audit` records audited commits and who vetted them.
+    // Defaults to "./audit-ledger.toml".
+    audit_ledger: Option<PathBuf>,
+    // Where `moz-wgpu audit` records the audited ranges.
+    // Defaults to "./audit-history.toml".
+    audit_history: Option<PathBuf>,
     // This parameter allows the wgpu-update command to override the wgpu repository url and
     // point to a wgpu fork (typically for testing purposes).
     // For regular use cases it is fine to let it unset by default.
@@ -206,12 +268,18 @@ fn shell(directory: &Path, cmd: &str, args: &[&str]) -> io::Result<ExitStatus> {
         cmd_str.push_str(arg);
         cmd_str.push(' ');
     }
-    println!(" -- Running {cmd_str:?}");
+    progress!(" -- Running {cmd_str:?}");
+
+    let stdout = if progress_on_stderr() {
+        Stdio::from(io::stderr())
+    } else {
+        Stdio::inherit()
+    };
 
     let status = Command::new(cmd)
         .args(args)
         .current_dir(directory)
-        .stdout(Stdio::inherit())
+        .stdout(stdout)
         .stderr(Stdio::inherit())
         .status();
     set_current_dir(old_cwd).unwrap();
@@ -235,7 +303,7 @@ fn read_shell(directory: &Path, cmd: &str, args: &[&str]) -> ShellOutput {
         cmd_str.push_str(arg);
         cmd_str.push(' ');
     }
-    println!(" -- Running {cmd_str:?}");
+    progress!(" -- Running {cmd_str:?}");
 
     let output = Command::new(cmd)
         .args(args)
@@ -253,7 +321,7 @@ fn read_shell(directory: &Path, cmd: &str, args: &[&str]) -> ShellOutput {
 pub fn concat_path(a: &Path, b: &str) -> PathBuf {
     let mut path = a.to_path_buf();
     if !b.is_empty() {
-        path.push(&PathBuf::from(b));
+        path.push(PathBuf::from(b));
     }
 
     path
@@ -263,9 +331,13 @@ fn main() -> io::Result<()> {
     match &Args::parse() {
         Args::WgpuUpdate(args) => wgpu_update::update_command(args),
         Args::Bugzilla(args) => helpers::file_bug(args),
-        Args::Audit(args) => audit::find_commits_to_audit(args),
+        Args::Audit(args) => audit::command(args),
         Args::Mach(args) => helpers::run_mach_command(args),
-        Args::Try { rebuild } => helpers::push_to_try(*rebuild),
+        Args::Try {
+            command: Some(command),
+            ..
+        } => helpers::try_command(command),
+        Args::Try { rebuild, .. } => helpers::push_to_try(*rebuild),
         Args::Histedit => helpers::hg_histedit(),
         Args::SelfUpdate => self_update(),
         Args::Cts(args) => cts::command(args),
@@ -0,0 +1,317 @@
+use futures::{stream, StreamExt};
+use serde::de::DeserializeOwned;
+use serde_derive::Deserialize;
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::Path,
+};
+
+pub const DEFAULT_ROOT_URL: &str = "https://firefox-ci-tc.services.mozilla.com";
+
+/// The number of artifacts downloaded at the same time.
+const PARALLEL_DOWNLOADS: usize = 8;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct IndexedTask {
+    task_id: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TaskGroup {
+    tasks: Vec<TaskGroupEntry>,
+    continuation_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TaskGroupEntry {
+    status: TaskStatus,
+    task: TaskDefinition,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TaskStatus {
+    task_id: String,
+    #[serde(default)]
+    runs: Vec<TaskRun>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TaskRun {
+    run_id: u32,
+    /// `pending`, `running`, `completed`, `failed` or `exception`.
+    state: String,
+}
+
+#[derive(Deserialize)]
+struct TaskDefinition {
+    metadata: TaskMetadata,
+}
+
+#[derive(Deserialize)]
+struct TaskMetadata {
+    /// For example `test-linux1804-64-qr/opt-web-platform-tests-webgpu-3`.
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Artifacts {
+    artifacts: Vec<Artifact>,
+    continuation_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Artifact {
+    name: String,
+}
+
+/// A wptreport artifact of a WebGPU test task.
+struct ReportArtifact {
+    task_id: String,
+    run_id: u32,
+    name: String,
+    /// The name of the downloaded file: the job name followed by the task and run ids, for
+    /// example `test-linux1804-64-qr-opt-web-platform-tests-webgpu-3.Hy4YQHhSQTWmyQNgVJ0m0g.0.json`,
+    /// with a `.N` suffix before the extension when the run has several reports.
+    file_name: String,
+}
+
+fn http_error(e: reqwest::Error) -> io::Error {
+    match e.status() {
+        Some(reqwest::StatusCode::NOT_FOUND) => io::Error::new(io::ErrorKind::NotFound, e),
+        _ => io::Error::other(e),
+    }
+}
+
+pub struct Taskcluster {
+    runtime: tokio::runtime::Runtime,
+    client: reqwest::Client,
+    root_url: String,
+}
+
+impl Taskcluster {
+    pub fn new(root_url: &str) -> io::Result<Self> {
+        Ok(Taskcluster {
+            runtime: tokio::runtime::Builder::new_current_thread()
+                .enable_io()
+                .enable_time()
+                .build()?,
+            client: reqwest::Client::new(),
+            root_url: root_url.trim_end_matches('/').to_string(),
+        })
+    }
+
+    async fn get_json<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        continuation_token: Option<&str>,
+    ) -> io::Result<T> {
+        let mut request = self.client.get(format!("{}/api/{path}", self.root_url));
+        if let Some(token) = continuation_token {
+            request = request.query(&[("continuationToken", token)]);
+        }
+
+        request
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(http_error)?
+            .json()
+            .await
+            .map_err(http_error)
+    }
+
+    /// The id of the decision task of a try push, which is also the id of its task group.
+    async fn decision_task(&self, rev: &str) -> io::Result<String> {
+        let path = format!("index/v1/task/gecko.v2.try.revision.{rev}.taskgraph.decision");
+        match self.get_json::<IndexedTask>(&path, None).await {
+            Ok(task) => Ok(task.task_id),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No try push found for revision {rev}"),
+            )),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn task_group(&self, group_id: &str) -> io::Result<Vec<TaskGroupEntry>> {
+        let path = format!("queue/v1/task-group/{group_id}/list");
+        let mut tasks = Vec::new();
+        let mut token = None;
+        loop {
+            let page: TaskGroup = self.get_json(&path, token.as_deref()).await?;
+            tasks.extend(page.tasks);
+            token = page.continuation_token;
+            if token.is_none() {
+                return Ok(tasks);
+            }
+        }
+    }
+
+    async fn artifact_names(&self, task_id: &str, run_id: u32) -> io::Result<Vec<String>> {
+        let path = format!("queue/v1/task/{task_id}/runs/{run_id}/artifacts");
+        let mut names = Vec::new();
+        let mut token = None;
+        loop {
+            let page: Artifacts = self.get_json(&path, token.as_deref()).await?;
+            names.extend(page.artifacts.into_iter().map(|artifact| artifact.name));
+            token = page.continuation_token;
+            if token.is_none() {
+                return Ok(names);
+            }
+        }
+    }
+
+    /// The wptreport artifacts of the last finished run of each WebGPU test task of the push.
+    async fn report_artifacts(&self, rev: &str) -> io::Result<Vec<ReportArtifact>> {
+        let group_id = self.decision_task(rev).await?;
+        let mut tasks: Vec<TaskGroupEntry> = self
+            .task_group(&group_id)
+            .await?
+            .into_iter()
+            .filter(|entry| {
+                entry
+                    .task
+                    .metadata
+                    .name
+                    .contains("web-platform-tests-webgpu")
+            })
+            .collect();
+        tasks.sort_by(|a, b| a.status.task_id.cmp(&b.status.task_id));
+
+        let mut artifacts = Vec::new();
+        for entry in tasks {
+            let Some(run) = entry
+                .status
+                .runs
+                .iter()
+                .rev()
+                .find(|run| run.state == "completed" || run.state == "failed")
+            else {
+                println!(
+                    " -- skipping {}, which has not finished",
+                    entry.task.metadata.name
+                );
+                continue;
+            };
+
+            let task_id = &entry.status.task_id;
+            let job = entry.task.metadata.name.replace('/', "-");
+            let names = self.artifact_names(task_id, run.run_id).await?;
+            let reports = names
+                .into_iter()
+                .filter(|name| name.ends_with("wptreport.json"));
+            for (idx, name) in reports.enumerate() {
+                let file_name = match idx {
+                    0 => format!("{job}.{task_id}.{}.json", run.run_id),
+                    n => format!("{job}.{task_id}.{}.{n}.json", run.run_id),
+                };
+                artifacts.push(ReportArtifact {
+                    task_id: task_id.clone(),
+                    run_id: run.run_id,
+                    name,
+                    file_name,
+                });
+            }
+        }
+
+        Ok(artifacts)
+    }
+
+    /// Download an artifact into `dir`, resuming from the `.part` file left by an interrupted
+    /// download. Returns the size of the file.
+    async fn download(&self, artifact: &ReportArtifact, dir: &Path) -> io::Result<u64> {
+        let path = dir.join(&artifact.file_name);
+        if let Ok(metadata) = std::fs::metadata(&path) {
+            return Ok(metadata.len());
+        }
+
+        let part = dir.join(format!("{}.part", artifact.file_name));
+        let mut size = std::fs::metadata(&part).map(|m| m.len()).unwrap_or(0);
+
+        let url = format!(
+            "{}/api/queue/v1/task/{}/runs/{}/artifacts/{}",
+            self.root_url, artifact.task_id, artifact.run_id, artifact.name
+        );
+        let mut request = self.client.get(url);
+        if size > 0 {
+            request = request.header(reqwest::header::RANGE, format!("bytes={size}-"));
+        }
+        let response = request.send().await.map_err(http_error)?;
+
+        // The previous download had actually received everything.
+        if size > 0 && response.status() == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+            std::fs::rename(&part, &path)?;
+            return Ok(size);
+        }
+        let mut response = response.error_for_status().map_err(http_error)?;
+
+        let mut file = if size > 0 && response.status() == reqwest::StatusCode::PARTIAL_CONTENT {
+            OpenOptions::new().append(true).open(&part)?
+        } else {
+            // The server ignored the range, start over.
+            size = 0;
+            File::create(&part)?
+        };
+        while let Some(chunk) = response.chunk().await.map_err(http_error)? {
+            file.write_all(&chunk)?;
+            size += chunk.len() as u64;
+        }
+        file.flush()?;
+        std::fs::rename(&part, &path)?;
+
+        Ok(size)
+    }
+
+    /// Download the WebGPU wptreport artifacts of a try push into `dir`.
+    ///
+    /// Files that were already downloaded are skipped and interrupted downloads are resumed,
+    /// so running this again after a failure only fetches what is missing.
+    pub fn fetch_reports(&self, rev: &str, dir: &Path) -> io::Result<()> {
+        self.runtime.block_on(async {
+            println!(" -- looking for the WebGPU test tasks of try:{rev}");
+            let artifacts = self.report_artifacts(rev).await?;
+            if artifacts.is_empty() {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("No WebGPU wptreport artifact in try:{rev}"),
+                ));
+            }
+
+            let count = artifacts.len();
+            println!(" -- downloading {count} wptreport artifacts");
+            let mut downloads = stream::iter(&artifacts)
+                .map(|artifact| async move { (artifact, self.download(artifact, dir).await) })
+                .buffer_unordered(PARALLEL_DOWNLOADS);
+
+            let (mut done, mut failed) = (0, 0);
+            while let Some((artifact, result)) = downloads.next().await {
+                done += 1;
+                match result {
+                    Ok(size) => println!(
+                        "[{done}/{count}] {} ({} KiB)",
+                        artifact.file_name,
+                        size.div_ceil(1024)
+                    ),
+                    Err(e) => {
+                        failed += 1;
+                        println!("[{done}/{count}] {} failed: {e}", artifact.file_name);
+                    }
+                }
+            }
+
+            if failed > 0 {
+                return Err(io::Error::other(format!(
+                    "{failed} downloads failed, fetch again to resume them"
+                )));
+            }
+
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,250 @@
+use serde::de::DeserializeOwned;
+use serde_derive::Deserialize;
+use std::{collections::BTreeMap, fmt::Write, io};
+
+pub const DEFAULT_URL: &str = "https://treeherder.mozilla.org";
+
+/// The number of jobs requested at a time.
+const JOBS_PAGE_SIZE: usize = 2000;
+
+#[derive(Deserialize)]
+struct Pushes {
+    results: Vec<Push>,
+}
+
+#[derive(Deserialize)]
+struct Push {
+    id: u64,
+}
+
+/// Treeherder sends each job as an array of values, described by `job_property_names`.
+#[derive(Deserialize)]
+struct Jobs {
+    results: Vec<Vec<serde_json::Value>>,
+    job_property_names: Vec<String>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum JobStatus {
+    Pending,
+    Running,
+    Passed,
+    Failed,
+    /// Retried, cancelled or with an unknown result.
+    Other,
+}
+
+#[derive(Clone, Debug)]
+pub struct Job {
+    /// For example `test-linux1804-64-qr/opt-web-platform-tests-webgpu-3`.
+    pub name: String,
+    /// For example `linux1804-64-qr/opt`.
+    pub platform: String,
+    /// `pending`, `running` or `completed`.
+    pub state: String,
+    /// `success`, `testfailed`, `busted`, `exception`, `retry`, `usercancel` or `unknown`.
+    pub result: String,
+}
+
+impl Job {
+    pub fn status(&self) -> JobStatus {
+        match (self.state.as_str(), self.result.as_str()) {
+            ("pending", _) => JobStatus::Pending,
+            ("running", _) => JobStatus::Running,
+            (_, "success") => JobStatus::Passed,
+            (_, "testfailed" | "busted" | "exception") => JobStatus::Failed,
+            _ => JobStatus::Other,
+        }
+    }
+
+    /// The test suite without the platform and chunk, for example `web-platform-tests-webgpu`.
+    pub fn suite(&self) -> &str {
+        let name = self.name.rsplit('/').next().unwrap_or(&self.name);
+        let name = match name.split_once('-') {
+            Some(("opt" | "debug", suite)) => suite,
+            _ => name,
+        };
+
+        match name.rsplit_once('-') {
+            Some((suite, chunk)) if chunk.parse::<u32>().is_ok() => suite,
+            _ => name,
+        }
+    }
+
+    pub fn is_webgpu(&self) -> bool {
+        self.name.contains("webgpu")
+    }
+}
+
+/// Network failures and server errors are likely to go away when the request is made again
+/// later, they are reported as `Interrupted`.
+fn http_error(e: reqwest::Error) -> io::Error {
+    let transient = e.is_timeout()
+        || e.is_connect()
+        || e.is_request()
+        || e.is_body()
+        || e.status().is_some_and(|status| {
+            status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        });
+
+    if transient {
+        io::Error::new(io::ErrorKind::Interrupted, e)
+    } else {
+        io::Error::other(e)
+    }
+}
+
+/// Whether a request to Treeherder may succeed when it is made again later. A push that is not
+/// found may not have been ingested by Treeherder yet.
+pub fn is_transient(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::Interrupted | io::ErrorKind::NotFound
+    )
+}
+
+pub struct Treeherder {
+    runtime: tokio::runtime::Runtime,
+    client: reqwest::Client,
+    url: String,
+}
+
+impl Treeherder {
+    pub fn new(url: &str) -> io::Result<Self> {
+        Ok(Treeherder {
+            runtime: tokio::runtime::Builder::new_current_thread()
+                .enable_io()
+                .enable_time()
+                .build()?,
+            // Treeherder rejects requests without a user agent.
+            client: reqwest::Client::builder()
+                .user_agent("moz-wgpu")
+                .build()
+                .map_err(io::Error::other)?,
+            url: url.trim_end_matches('/').to_string(),
+        })
+    }
+
+    async fn get_json<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, String)],
+    ) -> io::Result<T> {
+        self.client
+            .get(format!("{}/api/{path}", self.url))
+            .query(query)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(http_error)?
+            .json()
+            .await
+            .map_err(http_error)
+    }
+
+    /// All the jobs of a try push.
+    pub fn jobs(&self, rev: &str) -> io::Result<Vec<Job>> {
+        self.runtime.block_on(async {
+            let pushes: Pushes = self
+                .get_json("project/try/push/", &[("revision", rev.to_string())])
+                .await?;
+            let Some(push) = pushes.results.first() else {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("No try push found for revision {rev}"),
+                ));
+            };
+
+            let mut jobs = Vec::new();
+            loop {
+                let page: Jobs = self
+                    .get_json(
+                        "jobs/",
+                        &[
+                            ("push_id", push.id.to_string()),
+                            ("count", JOBS_PAGE_SIZE.to_string()),
+                            ("offset", jobs.len().to_string()),
+                        ],
+                    )
+                    .await?;
+                let page_len = page.results.len();
+
+                let property = |values: &[serde_json::Value], name: &str| -> String {
+                    page.job_property_names
+                        .iter()
+                        .position(|property| property == name)
+                        .and_then(|idx| values.get(idx))
+                        .and_then(|value| value.as_str())
+                        .unwrap_or_default()
+                        .to_string()
+                };
+                for values in &page.results {
+                    jobs.push(Job {
+                        name: property(values, "job_type_name"),
+                        platform: format!(
+                            "{}/{}",
+                            property(values, "platform"),
+                            property(values, "platform_option")
+                        ),
+                        state: property(values, "state"),
+                        result: property(values, "result"),
+                    });
+                }
+
+                if page_len < JOBS_PAGE_SIZE {
+                    return Ok(jobs);
+                }
+            }
+        })
+    }
+}
+
+/// The number of WebGPU jobs in each status, per platform and test suite.
+pub type StatusSummary = BTreeMap<(String, String), BTreeMap<JobStatus, usize>>;
+
+pub fn summarize(jobs: &[Job]) -> StatusSummary {
+    let mut summary = StatusSummary::new();
+    for job in jobs.iter().filter(|job| job.is_webgpu()) {
+        *summary
+            .entry((job.platform.clone(), job.suite().to_string()))
+            .or_default()
+            .entry(job.status())
+            .or_default() += 1;
+    }
+
+    summary
+}
+
+/// Whether all the jobs have finished.
+pub fn is_complete(summary: &StatusSummary) -> bool {
+    summary.values().all(|counts| {
+        !counts.contains_key(&JobStatus::Pending) && !counts.contains_key(&JobStatus::Running)
+    })
+}
+
+/// One line per platform and test suite, followed by the totals.
+pub fn format_summary(summary: &StatusSummary) -> String {
+    let mut text = String::new();
+    let mut totals: BTreeMap<JobStatus, usize> = BTreeMap::new();
+    let format_counts = |counts: &BTreeMap<JobStatus, usize>| {
+        let count = |status| counts.get(&status).copied().unwrap_or(0);
+        format!(
+            "pending {}, running {}, passed {}, failed {}, other {}",
+            count(JobStatus::Pending),
+            count(JobStatus::Running),
+            count(JobStatus::Passed),
+            count(JobStatus::Failed),
+            count(JobStatus::Other),
+        )
+    };
+
+    for ((platform, suite), counts) in summary {
+        let _ = writeln!(text, "{platform} {suite}: {}", format_counts(counts));
+        for (status, count) in counts {
+            *totals.entry(*status).or_default() += count;
+        }
+    }
+    let _ = writeln!(text, "\nTotal: {}", format_counts(&totals));
+
+    text
+}
@@ -1,6 +1,8 @@
 use crate::{
-    cargo_lock, cargo_toml, concat_path, moz_yaml, read_config_file, read_shell, shell, Vcs,
-    Version, DEFAULT_WGPU_REPOSITORY,
+    audit,
+    audit_ledger::{self, Ledger},
+    audit_unsafe, cargo_lock, cargo_toml, changelog, concat_path, moz_yaml, read_config_file,
+    read_shell, shell, GithubProject, Vcs, Version, DEFAULT_WGPU_REPOSITORY,
 };
 use clap::Parser;
 use std::{
@@ -50,6 +52,15 @@ pub struct Args {
     /// Skip the optional steps that ensure that the `gecko` directory is in an expected state.
     #[arg(long)]
     skip_preamble: bool,
+
+    /// Update even if some of the imported commits have not been vetted in the audit ledger.
+    #[arg(long)]
+    force: bool,
+
+    /// A file (for example produced by `moz-wgpu audit --changelog`) to append to the message
+    /// of the update commit.
+    #[arg(long, value_name = "FILE")]
+    changelog: Option<PathBuf>,
 }
 
 // For convenience, merge Config and Args into a single Param
@@ -63,6 +74,9 @@ pub struct Parameters {
     repository: String,
     preamble: bool,
     build: bool,
+    force: bool,
+    changelog: Option<String>,
+    wgpu: GithubProject,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -131,12 +145,29 @@ fn get_parameters(args: &Args) -> io::Result<Parameters> {
         repository,
         build: args.build,
         preamble: !args.skip_preamble,
+        force: args.force,
+        changelog: args
+            .changelog
+            .as_ref()
+            .map(std::fs::read_to_string)
+            .transpose()?,
+        wgpu: config.wgpu,
     })
 }
 
 pub fn update_command(args: &Args) -> io::Result<()> {
     let params = get_parameters(args)?;
 
+    check_audit(&params)?;
+
+    // The previous revision comes from gecko's Cargo.lock, so the changelog is read before the
+    // update changes it. The summary is only informational, failing to produce it does not stop
+    // the update.
+    let changelog_summary = summarize_changelog(&params).unwrap_or_else(|e| {
+        println!("Warning: could not summarize wgpu's CHANGELOG.md: {e}");
+        format!("Could not read the changelog ({e}), check it manually.\n")
+    });
+
     if params.preamble {
         preamble(&params)?;
     }
@@ -153,11 +184,15 @@ pub fn update_command(args: &Args) -> io::Result<()> {
 
     vendor_wgpu_update(&params)?;
 
+    println!("\n# `wgpu` CHANGELOG.md entries since the previous update\n");
+    println!("{changelog_summary}");
+
     if params.build {
         build(&params)?;
     }
 
     println!("\n\nAll done!");
+
     if !params.build {
         println!(
             "Now is a good time to do a `./mach build` in case there were breaking changes in \
@@ -170,6 +205,78 @@ pub fn update_command(args: &Args) -> io::Result<()> {
     Ok(())
 }
 
+/// Summarize the entries added to wgpu's `CHANGELOG.md` between the revision in gecko and the
+/// new revision, so that breaking changes in `wgpu-core`'s API do not come as a surprise.
+fn summarize_changelog(params: &Parameters) -> io::Result<String> {
+    let prev = cargo_lock::find_version("wgpu-core", &params.gecko_path)?;
+
+    if prev.git_hash.is_empty() {
+        return Ok(
+            "The previous `wgpu-core` was not imported from git, cannot diff the changelog.\n"
+                .to_string(),
+        );
+    }
+
+    let entries =
+        changelog::added_changelog_entries(&params.wgpu.path, &prev.git_hash, &params.wgpu_rev)?;
+    Ok(changelog::summarize_changelog(&entries))
+}
+
+/// Refuse to update if the commits that would be imported have not all been vetted.
+fn check_audit(params: &Parameters) -> io::Result<()> {
+    let prev = cargo_lock::find_version("wgpu-core", &params.gecko_path)?;
+    let next = &params.wgpu_rev;
+
+    if prev.git_hash.is_empty() {
+        println!(
+            "`wgpu-core` {} is not imported from git, cannot determine which commits to check in the audit ledger.",
+            prev.semver
+        );
+        if params.force {
+            return Ok(());
+        }
+        return Err(io::Error::other(
+            "Could not verify the audit, pass --force to update anyway",
+        ));
+    }
+
+    println!(
+        "Checking the audit ledger for commits {}..{next}",
+        prev.git_hash
+    );
+    let status = Ledger::load(&audit_ledger::ledger_path(&params.wgpu))
+        .and_then(|ledger| audit::range_status(&params.wgpu, &ledger, &prev.git_hash, next));
+    let status = match status {
+        Ok(status) => status,
+        Err(e) => {
+            println!("Could not check the commits in the audit ledger: {e}");
+            if params.force {
+                println!("Updating anyway (--force).");
+                return Ok(());
+            }
+            return Err(io::Error::other(
+                "Could not verify the audit, pass --force to update anyway",
+            ));
+        }
+    };
+
+    if status.is_complete() {
+        println!("All {} commits have been vetted.", status.commit_count);
+        return Ok(());
+    }
+
+    audit::print_range_status(&status, &prev.git_hash, next)?;
+
+    if params.force {
+        println!("Updating anyway (--force).");
+        return Ok(());
+    }
+
+    Err(io::Error::other(
+        "Some commits have not been vetted, audit them first or pass --force to update anyway",
+    ))
+}
+
 /// Do a few things to make sure we start in a good state.
 fn preamble(params: &Parameters) -> io::Result<()> {
     let vcs = match params.vcs {
@@ -252,11 +359,13 @@ fn update_wgpu(params: &Parameters) -> io::Result<Vec<Delta>> {
 
     refresh_cargo_lock(&params.gecko_path, &params.wgpu_rev);
 
-    let commit = commit(
-        params,
-        &format!("Update `wgpu` to revision {wgpu_rev}. r=#webgpu-reviewers"),
-        COMMIT_UPADTE,
-    )?;
+    let mut msg = format!("Update `wgpu` to revision {wgpu_rev}. r=#webgpu-reviewers");
+    if let Some(changelog) = &params.changelog {
+        msg.push_str("\n\n");
+        msg.push_str(changelog.trim());
+    }
+
+    let commit = commit(params, &msg, COMMIT_UPADTE)?;
     assert!(commit.success());
 
     // println!("Parsing new crate versions from `Cargo.lock`");
@@ -357,6 +466,46 @@ fn vendor_wgpu_update(params: &Parameters) -> io::Result<()> {
     Ok(())
 }
 
+/// The message of the vet commit, with a summary of the unsafe code added and removed by the
+/// imported `wgpu` commits.
+fn vet_commit_message(params: &Parameters, deltas: &[Delta]) -> String {
+    let mut msg = "Vet `wgpu` and `naga` commits. r=#supply-chain-reviewers".to_string();
+
+    let prev = deltas
+        .iter()
+        .find(|delta| delta.name == "wgpu-core")
+        .map(|delta| delta.prev.git_hash.clone())
+        .unwrap_or_default();
+    if prev.is_empty() {
+        return msg;
+    }
+
+    // Merge commits are skipped since their diff repeats the changes of the commits they merged.
+    let commits =
+        audit::git_rev_list(&params.wgpu.path, &prev, &params.wgpu_rev).and_then(|commits| {
+            let merges = audit::git_merge_commits(&params.wgpu.path, &prev, &params.wgpu_rev)?;
+            Ok(commits
+                .into_iter()
+                .filter(|hash| !merges.contains(hash))
+                .collect::<Vec<_>>())
+        });
+
+    match commits {
+        Ok(commits) => {
+            let churn = audit_unsafe::unsafe_churn_for_commits(&params.wgpu.path, &commits);
+            msg.push_str(&format!(
+                "\n\nUnsafe code changes in {} non-merge commits ({prev}..{}):\n",
+                commits.len(),
+                params.wgpu_rev,
+            ));
+            msg.push_str(&audit_unsafe::format_unsafe_churn(&churn));
+        }
+        Err(e) => println!("Could not count unsafe code changes: {e}"),
+    }
+
+    msg
+}
+
 fn vet_delta(params: &Parameters, deltas: &[Delta]) -> io::Result<()> {
     for delta in deltas {
         let crate_name = &delta.name;
@@ -385,11 +534,7 @@ fn vet_delta(params: &Parameters, deltas: &[Delta]) -> io::Result<()> {
         assert!(vet.success());
     }
 
-    let commit = commit(
-        params,
-        "Vet `wgpu` and `naga` commits. r=#supply-chain-reviewers",
-        COMMIT_AUDIT,
-    )?;
+    let commit = commit(params, &vet_commit_message(params, deltas), COMMIT_AUDIT)?;
     assert!(commit.success());
 
     let _ = shell(&params.gecko_path, "./mach", &["cargo", "vet"]);
@@ -448,11 +593,7 @@ fn vet_from_base_revision(params: &Parameters, deltas: &[Delta]) -> io::Result<(
         assert!(vet.success());
     }
 
-    let commit = commit(
-        params,
-        "Vet `wgpu` and `naga` commits. r=#supply-chain-reviewers",
-        COMMIT_AUDIT,
-    )?;
+    let commit = commit(params, &vet_commit_message(params, deltas), COMMIT_AUDIT)?;
     assert!(commit.success());
 
     // Run cargo vet to see if there are any other new crate versions that were imported
@@ -467,20 +608,37 @@ fn build(params: &Parameters) -> io::Result<ExitStatus> {
     shell(&params.gecko_path, "./mach", &["build"])
 }
 
-fn commit(params: &Parameters, msg: &str, commit_idx: Option<usize>) -> io::Result<ExitStatus> {
+/// Prefix the message with the bug number and link the Phabricator revision, if any.
+pub fn commit_message(
+    bug: Option<&str>,
+    phab_revisions: Option<&[String]>,
+    msg: &str,
+    commit_idx: Option<usize>,
+) -> String {
     let mut commit_msg = String::new();
-    if let Some(bug) = &params.bug {
+    if let Some(bug) = bug {
         commit_msg.push_str(&format!("Bug {bug} - "));
     }
     commit_msg.push_str(msg);
 
-    if let (Some(revs), Some(idx)) = (&params.phab_revisions, commit_idx) {
+    if let (Some(revs), Some(idx)) = (phab_revisions, commit_idx) {
         commit_msg.push_str(&format!(
             "\n\nDifferential Revision: https://phabricator.services.mozilla.com/{}",
             revs[idx]
         ));
     }
 
+    commit_msg
+}
+
+fn commit(params: &Parameters, msg: &str, commit_idx: Option<usize>) -> io::Result<ExitStatus> {
+    let commit_msg = commit_message(
+        params.bug.as_deref(),
+        params.phab_revisions.as_ref().map(|revs| &revs[..]),
+        msg,
+        commit_idx,
+    );
+
     let mc = &params.gecko_path;
     match params.vcs {
         Vcs::Mercurial => shell(mc, "hg", &["commit", "-m", &commit_msg]),
@@ -0,0 +1,638 @@
+use serde_derive::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    fmt::Write,
+    fs::File,
+    io,
+    path::{Path, PathBuf},
+};
+
+/// The status of a test or subtest, as reported by wptrunner.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Status {
+    Pass,
+    Fail,
+    Ok,
+    Error,
+    Timeout,
+    Crash,
+    Skip,
+    Notrun,
+    PreconditionFailed,
+    #[serde(other)]
+    Unknown,
+}
+
+impl Status {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Status::Pass => "PASS",
+            Status::Fail => "FAIL",
+            Status::Ok => "OK",
+            Status::Error => "ERROR",
+            Status::Timeout => "TIMEOUT",
+            Status::Crash => "CRASH",
+            Status::Skip => "SKIP",
+            Status::Notrun => "NOTRUN",
+            Status::PreconditionFailed => "PRECONDITION_FAILED",
+            Status::Unknown => "UNKNOWN",
+        }
+    }
+
+    pub fn parse(text: &str) -> Option<Self> {
+        serde_json::from_value(serde_json::Value::String(text.to_string()))
+            .ok()
+            .filter(|status| *status != Status::Unknown)
+    }
+
+    /// Whether the test (or subtest) ran to completion and was successful.
+    pub fn is_success(self) -> bool {
+        matches!(self, Status::Pass | Status::Ok)
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct SubtestResult {
+    pub name: String,
+    pub status: Status,
+    /// Only present when the status is not the expected one.
+    pub expected: Option<Status>,
+    #[serde(default)]
+    pub known_intermittent: Vec<Status>,
+}
+
+impl SubtestResult {
+    pub fn expected(&self) -> Status {
+        self.expected.unwrap_or(self.status)
+    }
+
+    pub fn is_unexpected(&self) -> bool {
+        self.status != self.expected() && !self.known_intermittent.contains(&self.status)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct TestResult {
+    /// The test's URL, for example
+    /// `/_mozilla/webgpu/cts/webgpu/api/validation/buffer/destroy/cts.https.html?q=webgpu:api,validation,buffer,destroy:*`.
+    pub test: String,
+    pub status: Status,
+    /// Only present when the status is not the expected one.
+    pub expected: Option<Status>,
+    #[serde(default)]
+    pub known_intermittent: Vec<Status>,
+    #[serde(default)]
+    pub subtests: Vec<SubtestResult>,
+}
+
+impl TestResult {
+    pub fn expected(&self) -> Status {
+        self.expected.unwrap_or(self.status)
+    }
+
+    pub fn is_unexpected(&self) -> bool {
+        self.status != self.expected() && !self.known_intermittent.contains(&self.status)
+    }
+
+    /// The CTS query of the test, for example `webgpu:api,validation,buffer,destroy:*`.
+    pub fn query(&self) -> &str {
+        match self.test.split_once("?q=") {
+            Some((_, query)) => query,
+            None => &self.test,
+        }
+    }
+}
+
+/// The expectation metadata file of a test (relative to gecko's root) and the name of the test's
+/// section in it, for example
+/// `testing/web-platform/mozilla/meta/webgpu/cts/webgpu/api/validation/buffer/destroy/cts.https.html.ini`
+/// and `cts.https.html?q=webgpu:api,validation,buffer,destroy:*`.
+pub fn metadata_location(test: &str) -> (PathBuf, String) {
+    let (path, query) = match test.split_once('?') {
+        Some((path, query)) => (path, format!("?{query}")),
+        None => (test, String::new()),
+    };
+
+    let (meta_dir, path) = match path.strip_prefix("/_mozilla/") {
+        Some(path) => ("testing/web-platform/mozilla/meta", path),
+        None => ("testing/web-platform/meta", path.trim_start_matches('/')),
+    };
+
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+    let file = Path::new(meta_dir).join(format!("{path}.ini"));
+
+    (file, format!("{file_name}{query}"))
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BuildType {
+    Debug,
+    Opt,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Platform {
+    /// `linux`, `mac`, `win` or `android`.
+    pub os: String,
+    pub build_type: BuildType,
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let build_type = match self.build_type {
+            BuildType::Debug => "debug",
+            BuildType::Opt => "opt",
+        };
+        write!(f, "{}/{build_type}", self.os)
+    }
+}
+
+/// Everything that distinguishes the machines and builds the tests ran on. Unlike `Platform`,
+/// which only has what the expectation metadata conditions look at, two configurations with the
+/// same `Platform` can have different results.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Configuration {
+    pub platform: Platform,
+    pub os_version: String,
+    pub processor: String,
+    /// `x11` or `wayland` on Linux.
+    pub display: Option<String>,
+}
+
+impl fmt::Display for Configuration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.platform.os)?;
+        for part in [&self.os_version, &self.processor] {
+            if !part.is_empty() {
+                write!(f, " {part}")?;
+            }
+        }
+        if let Some(display) = &self.display {
+            write!(f, " {display}")?;
+        }
+        let build_type = match self.platform.build_type {
+            BuildType::Debug => "debug",
+            BuildType::Opt => "opt",
+        };
+        write!(f, "/{build_type}")
+    }
+}
+
+/// A configuration that only has the platform, for results combined across machines.
+impl From<Platform> for Configuration {
+    fn from(platform: Platform) -> Self {
+        Configuration {
+            platform,
+            os_version: String::new(),
+            processor: String::new(),
+            display: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct RunInfo {
+    #[serde(default)]
+    pub os: String,
+    #[serde(default)]
+    pub debug: bool,
+    #[serde(default)]
+    pub os_version: String,
+    #[serde(default)]
+    pub processor: String,
+    #[serde(default)]
+    pub display: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Report {
+    #[serde(default)]
+    pub run_info: RunInfo,
+    #[serde(default)]
+    pub results: Vec<TestResult>,
+}
+
+impl Report {
+    pub fn configuration(&self) -> Configuration {
+        Configuration {
+            platform: self.platform(),
+            os_version: self.run_info.os_version.clone(),
+            processor: self.run_info.processor.clone(),
+            display: self.run_info.display.clone(),
+        }
+    }
+
+    pub fn platform(&self) -> Platform {
+        Platform {
+            os: self.run_info.os.clone(),
+            build_type: if self.run_info.debug {
+                BuildType::Debug
+            } else {
+                BuildType::Opt
+            },
+        }
+    }
+}
+
+/// Parse a wptreport JSON file.
+pub fn read_report(path: &Path) -> io::Result<Report> {
+    let file = io::BufReader::new(File::open(path)?);
+    serde_json::from_reader(file).map_err(|e| {
+        let kind = if e.is_eof() {
+            io::ErrorKind::UnexpectedEof
+        } else {
+            io::ErrorKind::InvalidData
+        };
+        io::Error::new(kind, format!("Failed to parse {path:?}: {e}"))
+    })
+}
+
+/// Whether `text` looks like a Taskcluster task id (22 characters of URL-safe base64).
+fn is_task_id(text: &str) -> bool {
+    text.len() == 22
+        && text
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+/// The shard a report was produced by, from a file name such as
+/// `test-linux1804-64-qr-opt-web-platform-tests-webgpu-3.json`. The task id, run id and report
+/// index that `cts fetch` adds (as in
+/// `test-linux1804-64-qr-opt-web-platform-tests-webgpu-3.Hy4YQHhSQTWmyQNgVJ0m0g.0.json`) are
+/// ignored.
+fn shard_of(path: &Path) -> (String, Option<u32>) {
+    let mut stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default();
+    while let Some((rest, suffix)) = stem.rsplit_once('.') {
+        if suffix.parse::<u32>().is_err() {
+            break;
+        }
+        stem = rest;
+    }
+    if let Some((rest, task_id)) = stem.rsplit_once('.') {
+        if is_task_id(task_id) {
+            stem = rest;
+        }
+    }
+    let stem = stem.strip_suffix("-wptreport").unwrap_or(stem);
+
+    match stem.rsplit_once('-') {
+        Some((job, chunk)) => match chunk.parse() {
+            Ok(chunk) => (job.to_string(), Some(chunk)),
+            Err(_) => (stem.to_string(), None),
+        },
+        None => (stem.to_string(), None),
+    }
+}
+
+/// The wptreport files of a directory, with the problems found while loading them.
+#[derive(Default)]
+pub struct ReportSet {
+    pub reports: Vec<(PathBuf, Report)>,
+    /// Truncated or unreadable files, empty reports and missing shards.
+    pub problems: Vec<String>,
+}
+
+impl ReportSet {
+    /// Load all the `.json` files of `dir` (sorted by name).
+    pub fn load(dir: &Path) -> io::Result<Self> {
+        let mut paths = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            if entry.file_type()?.is_file()
+                && path.extension().and_then(|ext| ext.to_str()) == Some("json")
+            {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        let mut set = ReportSet::default();
+        if paths.is_empty() {
+            set.problems.push(format!("No wptreport file in {dir:?}"));
+        }
+
+        let mut chunks: BTreeMap<String, BTreeSet<u32>> = BTreeMap::new();
+        for path in paths {
+            let (job, chunk) = shard_of(&path);
+            if let Some(chunk) = chunk {
+                chunks.entry(job).or_default().insert(chunk);
+            }
+
+            match read_report(&path) {
+                Ok(report) if report.results.is_empty() => {
+                    set.problems.push(format!("{path:?} contains no results"));
+                }
+                Ok(report) => set.reports.push((path, report)),
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                    set.problems.push(format!("{path:?} is truncated"));
+                }
+                Err(e) => set.problems.push(e.to_string()),
+            }
+        }
+
+        // Chunks are numbered from 1. Missing chunks after the last one can't be detected.
+        for (job, chunks) in chunks {
+            let last = chunks.iter().next_back().copied().unwrap_or(0);
+            let missing: Vec<String> = (1..=last)
+                .filter(|chunk| !chunks.contains(chunk))
+                .map(|chunk| chunk.to_string())
+                .collect();
+            if !missing.is_empty() {
+                set.problems
+                    .push(format!("Missing chunks {} of {job}", missing.join(", ")));
+            }
+        }
+
+        Ok(set)
+    }
+
+    pub fn results_by_configuration(&self) -> impl Iterator<Item = (Configuration, &TestResult)> {
+        self.reports.iter().flat_map(|(_, report)| {
+            let configuration = report.configuration();
+            report
+                .results
+                .iter()
+                .map(move |result| (configuration.clone(), result))
+        })
+    }
+
+    pub fn results(&self) -> impl Iterator<Item = (Platform, &TestResult)> {
+        self.reports.iter().flat_map(|(_, report)| {
+            let platform = report.platform();
+            report
+                .results
+                .iter()
+                .map(move |result| (platform.clone(), result))
+        })
+    }
+}
+
+/// The results of the tests and subtests that ran on a platform.
+///
+/// Test and subtest statuses are counted separately: a test reports `OK` when it ran to
+/// completion, even if all of its subtests failed.
+#[derive(Default)]
+pub struct PlatformSummary {
+    pub tests: BTreeMap<Status, usize>,
+    pub subtests: BTreeMap<Status, usize>,
+    /// The number of tests whose status was not the expected one.
+    pub unexpected_tests: usize,
+    /// The number of subtests whose status was not the expected one.
+    pub unexpected_subtests: usize,
+    /// The queries of the tests that crashed.
+    pub crashes: Vec<String>,
+}
+
+pub fn summarize(set: &ReportSet) -> BTreeMap<Platform, PlatformSummary> {
+    let mut summaries: BTreeMap<Platform, PlatformSummary> = BTreeMap::new();
+    for (platform, result) in set.results() {
+        let summary = summaries.entry(platform).or_default();
+        *summary.tests.entry(result.status).or_default() += 1;
+        summary.unexpected_tests += result.is_unexpected() as usize;
+        if result.status == Status::Crash {
+            summary.crashes.push(result.query().to_string());
+        }
+
+        for subtest in &result.subtests {
+            *summary.subtests.entry(subtest.status).or_default() += 1;
+            summary.unexpected_subtests += subtest.is_unexpected() as usize;
+        }
+    }
+
+    summaries
+}
+
+/// For example `pass 12, fail 1, timeout 0, crash 0, other 2 (1 unexpected)`.
+fn format_counts(counts: &BTreeMap<Status, usize>, unexpected: usize) -> String {
+    let count = |statuses: &[Status]| -> usize {
+        statuses
+            .iter()
+            .map(|status| counts.get(status).copied().unwrap_or(0))
+            .sum()
+    };
+
+    format!(
+        "pass {}, fail {}, timeout {}, crash {}, other {} ({unexpected} unexpected)",
+        count(&[Status::Pass, Status::Ok]),
+        count(&[Status::Fail, Status::Error]),
+        count(&[Status::Timeout]),
+        count(&[Status::Crash]),
+        count(&[
+            Status::Skip,
+            Status::Notrun,
+            Status::PreconditionFailed,
+            Status::Unknown
+        ]),
+    )
+}
+
+/// A few lines per platform with the number of passing, failing, timing out and crashing tests
+/// and subtests, followed by the problems found while loading the reports.
+pub fn format_summary(set: &ReportSet) -> String {
+    let mut text = String::new();
+    for (platform, summary) in summarize(set) {
+        let _ = writeln!(text, "{platform}:");
+        let _ = writeln!(
+            text,
+            "  tests: {}",
+            format_counts(&summary.tests, summary.unexpected_tests)
+        );
+        let _ = writeln!(
+            text,
+            "  subtests: {}",
+            format_counts(&summary.subtests, summary.unexpected_subtests)
+        );
+        for query in &summary.crashes {
+            let _ = writeln!(text, "  crashed: {query}");
+        }
+    }
+
+    if !set.problems.is_empty() {
+        let _ = writeln!(text, "\nProblems:");
+        for problem in &set.problems {
+            let _ = writeln!(text, " * {problem}");
+        }
+    }
+
+    text
+}
+
+/// One line per test or subtest whose status was not the expected one.
+pub fn format_unexpected(set: &ReportSet) -> String {
+    let mut text = String::new();
+    for (platform, result) in set.results() {
+        if result.is_unexpected() {
+            let _ = writeln!(
+                text,
+                " * {} on {platform}: expected {}, got {}",
+                result.query(),
+                result.expected(),
+                result.status
+            );
+        }
+        for subtest in result
+            .subtests
+            .iter()
+            .filter(|subtest| subtest.is_unexpected())
+        {
+            let _ = writeln!(
+                text,
+                " * {} [{}] on {platform}: expected {}, got {}",
+                result.query(),
+                subtest.name,
+                subtest.expected(),
+                subtest.status
+            );
+        }
+    }
+
+    if text.is_empty() {
+        text.push_str("All tests and subtests had the expected status.\n");
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty directory for a test to write reports into.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("moz-wgpu-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    const REPORT: &str = r#"{
+        "run_info": { "os": "linux", "debug": false },
+        "results": [
+            {
+                "test": "/_mozilla/webgpu/cts/webgpu/api/validation/buffer/destroy/cts.https.html?q=webgpu:api,validation,buffer,destroy:*",
+                "status": "OK",
+                "subtests": [
+                    { "name": "a", "status": "PASS" },
+                    { "name": "b", "status": "FAIL", "expected": "PASS" }
+                ]
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn shard_names() {
+        let job = "test-linux1804-64-qr-opt-web-platform-tests-webgpu";
+        assert_eq!(
+            shard_of(Path::new(&format!("{job}-3.json"))),
+            (job.to_string(), Some(3))
+        );
+        assert_eq!(
+            shard_of(Path::new(&format!("{job}-3.1.json"))),
+            (job.to_string(), Some(3))
+        );
+        assert_eq!(
+            shard_of(Path::new(&format!("{job}-12-wptreport.json"))),
+            (job.to_string(), Some(12))
+        );
+        assert_eq!(
+            shard_of(Path::new(&format!("{job}-3.Hy4YQHhSQTWmyQNgVJ0m0g.0.json"))),
+            (job.to_string(), Some(3))
+        );
+        assert_eq!(
+            shard_of(Path::new(&format!(
+                "{job}-3.a-_YQHhSQTWmyQNgVJ0m0g.2.1.json"
+            ))),
+            (job.to_string(), Some(3))
+        );
+        let android = "test-android-em-7.0-x86_64-qr-opt-web-platform-tests-webgpu";
+        assert_eq!(
+            shard_of(Path::new(&format!(
+                "{android}-2.Hy4YQHhSQTWmyQNgVJ0m0g.1.json"
+            ))),
+            (android.to_string(), Some(2))
+        );
+        assert_eq!(
+            shard_of(Path::new("local.json")),
+            ("local".to_string(), None)
+        );
+    }
+
+    #[test]
+    fn missing_shards() {
+        let dir = test_dir("missing-shards");
+        let job = "test-linux1804-64-qr-opt-web-platform-tests-webgpu";
+        for chunk in [1, 3, 5] {
+            std::fs::write(dir.join(format!("{job}-{chunk}.json")), REPORT).unwrap();
+        }
+
+        let set = ReportSet::load(&dir).unwrap();
+        assert_eq!(set.reports.len(), 3);
+        assert_eq!(set.problems, vec![format!("Missing chunks 2, 4 of {job}")]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn truncated_and_empty_reports() {
+        let dir = test_dir("truncated");
+        let job = "test-windows11-64-24h2-opt-web-platform-tests-webgpu";
+        std::fs::write(dir.join(format!("{job}-1.json")), REPORT).unwrap();
+        std::fs::write(
+            dir.join(format!("{job}-2.json")),
+            &REPORT[..REPORT.len() / 2],
+        )
+        .unwrap();
+        std::fs::write(dir.join(format!("{job}-3.json")), r#"{"results": []}"#).unwrap();
+
+        let set = ReportSet::load(&dir).unwrap();
+        assert_eq!(set.reports.len(), 1);
+        assert_eq!(set.problems.len(), 2);
+        assert!(
+            set.problems[0].ends_with("is truncated"),
+            "{:?}",
+            set.problems
+        );
+        assert!(
+            set.problems[1].ends_with("contains no results"),
+            "{:?}",
+            set.problems
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let dir = test_dir("empty");
+        let set = ReportSet::load(&dir).unwrap();
+        assert_eq!(set.problems.len(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_and_subtest_counts_are_separate() {
+        let report: Report = serde_json::from_str(REPORT).unwrap();
+        let set = ReportSet {
+            reports: vec![(PathBuf::from("report.json"), report)],
+            problems: Vec::new(),
+        };
+
+        let summaries = summarize(&set);
+        let summary = summaries.values().next().unwrap();
+        assert_eq!(summary.tests.get(&Status::Ok), Some(&1));
+        assert_eq!(summary.tests.get(&Status::Pass), None);
+        assert_eq!(summary.subtests.get(&Status::Pass), Some(&1));
+        assert_eq!(summary.subtests.get(&Status::Fail), Some(&1));
+        assert_eq!(summary.unexpected_tests, 0);
+        assert_eq!(summary.unexpected_subtests, 1);
+    }
+}